@@ -4,6 +4,7 @@ use super::launcher::Input;
 
 pub trait App {
     fn draw(&mut self, display: &mut PixelDisplay);
+    fn needs_redraw(&mut self) -> bool;
     fn enable(&mut self);
     fn disable(&mut self);
     fn input(&mut self, input: Input);
@@ -0,0 +1,640 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::pixelcolor::Rgb888;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::config::{in_range, ConfigError};
+use crate::modules::image::{list_dir, Animation, Image};
+use crate::modules::ken_burns::KenBurnsSettings;
+use crate::pixel_display::font::FontRegistry;
+use crate::pixel_display::text_style::{TextColor, TextStyle};
+
+const SAKURA: &[u8; 6282] = include_bytes!("../../assets/sakura.bmp");
+const CLOUD: &[u8; 1186] = include_bytes!("../../assets/cloud.bmp");
+const FOREST: &[u8; 1190] = include_bytes!("../../assets/forest.bmp");
+const NIGHT: &[u8; 6282] = include_bytes!("../../assets/night.bmp");
+const ART: &[u8; 6282] = include_bytes!("../../assets/art.bmp");
+
+const BUNDLED: [(&str, &[u8], &str); 5] = [
+    ("sakura", SAKURA, include_str!("../../assets/sakura.toml")),
+    ("cloud", CLOUD, include_str!("../../assets/cloud.toml")),
+    ("forest", FOREST, include_str!("../../assets/forest.toml")),
+    ("night", NIGHT, include_str!("../../assets/night.toml")),
+    ("art", ART, include_str!("../../assets/art.toml")),
+];
+
+#[derive(Clone)]
+pub struct BackgroundSettings {
+    pub directory: Option<PathBuf>,
+    pub interval: Duration,
+}
+
+impl Default for BackgroundSettings {
+    fn default() -> Self {
+        BackgroundSettings {
+            directory: None,
+            interval: Duration::from_secs(45 * 60),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Tag {
+    Morning,
+    Day,
+    Evening,
+    Night,
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+    // A holiday, like 12-25 for Christmas.
+    Date { month: u32, day: u32 },
+}
+
+#[derive(PartialEq)]
+enum TagKind {
+    TimeOfDay,
+    Season,
+    Date,
+}
+
+impl Tag {
+    fn kind(&self) -> TagKind {
+        match self {
+            Tag::Morning | Tag::Day | Tag::Evening | Tag::Night => TagKind::TimeOfDay,
+            Tag::Spring | Tag::Summer | Tag::Autumn | Tag::Winter => TagKind::Season,
+            Tag::Date { .. } => TagKind::Date,
+        }
+    }
+
+    fn matches(&self, now: NaiveDateTime) -> bool {
+        match self.kind() {
+            TagKind::TimeOfDay => time_of_day(now.hour()) == *self,
+            TagKind::Season => season(now.month()) == *self,
+            TagKind::Date => {
+                *self
+                    == Tag::Date {
+                        month: now.month(),
+                        day: now.day(),
+                    }
+            }
+        }
+    }
+}
+
+fn time_of_day(hour: u32) -> Tag {
+    match hour {
+        5..=9 => Tag::Morning,
+        10..=16 => Tag::Day,
+        17..=20 => Tag::Evening,
+        _ => Tag::Night,
+    }
+}
+
+// Meteorological seasons of the northern hemisphere.
+fn season(month: u32) -> Tag {
+    match month {
+        3..=5 => Tag::Spring,
+        6..=8 => Tag::Summer,
+        9..=11 => Tag::Autumn,
+        _ => Tag::Winter,
+    }
+}
+
+impl FromStr for Tag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "morning" => Ok(Tag::Morning),
+            "day" => Ok(Tag::Day),
+            "evening" => Ok(Tag::Evening),
+            "night" => Ok(Tag::Night),
+            "spring" => Ok(Tag::Spring),
+            "summer" => Ok(Tag::Summer),
+            "autumn" => Ok(Tag::Autumn),
+            "winter" => Ok(Tag::Winter),
+            _ => NaiveDate::parse_from_str(&format!("2024-{}", s), "%Y-%m-%d")
+                .map(|date| Tag::Date {
+                    month: date.month(),
+                    day: date.day(),
+                })
+                .map_err(|_| {
+                    format!(
+                        "unknown tag '{}', expected a time of day, season or MM-DD",
+                        s
+                    )
+                }),
+        }
+    }
+}
+
+// Where and in which colors the time and date go on top of a background.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Metadata {
+    pub time: Point,
+    pub date: Point,
+    pub time_style: TextStyle,
+    pub date_style: TextStyle,
+    pub tags: Vec<Tag>,
+    pub animation: Animation,
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Metadata {
+            time: Point::new(2, 6),
+            date: Point::new(23, 6),
+            time_style: TextStyle::default(),
+            date_style: TextStyle::default(),
+            tags: Vec::new(),
+            animation: Animation::default(),
+        }
+    }
+}
+
+impl Metadata {
+    // Tags of the same kind are alternatives, a background for night or evening in winter shows on
+    // winter evenings and nights. Without tags of a kind any time fits.
+    pub fn matches(&self, now: NaiveDateTime) -> bool {
+        [TagKind::TimeOfDay, TagKind::Season, TagKind::Date]
+            .iter()
+            .all(|kind| {
+                let mut tags = self
+                    .tags
+                    .iter()
+                    .filter(|tag| tag.kind() == *kind)
+                    .peekable();
+                tags.peek().is_none() || tags.any(|tag| tag.matches(now))
+            })
+    }
+
+    fn is_holiday(&self) -> bool {
+        self.tags.iter().any(|tag| tag.kind() == TagKind::Date)
+    }
+
+    fn parse(path: &Path, contents: &str, fonts: &FontRegistry) -> Result<Metadata, ConfigError> {
+        toml::from_str::<MetadataFile>(contents)
+            .map_err(|e| ConfigError::Parse(path.to_path_buf(), e))?
+            .validate(fonts)
+    }
+
+    // The metadata of `sunset.png` is read from `sunset.toml`, without one the defaults apply.
+    fn read(image: &Path, fonts: &FontRegistry) -> Result<Metadata, ConfigError> {
+        let path = image.with_extension("toml");
+        if !path.exists() {
+            return Ok(Metadata::default());
+        }
+        let contents = fs::read_to_string(&path).map_err(|e| ConfigError::Read(path.clone(), e))?;
+
+        Metadata::parse(&path, &contents, fonts)
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct MetadataFile {
+    time: Option<[i32; 2]>,
+    date: Option<[i32; 2]>,
+    time_color: Option<String>,
+    date_color: Option<String>,
+    time_font: Option<String>,
+    date_font: Option<String>,
+    outline: Option<String>,
+    shadow: Option<String>,
+    tags: Vec<String>,
+    frame_size: Option<[u32; 2]>,
+    frames: Option<usize>,
+    frame_ms: Vec<u32>,
+    pan: Option<String>,
+    pan_seconds: Option<u32>,
+    zoom: Option<[f32; 2]>,
+}
+
+impl MetadataFile {
+    fn validate(self, fonts: &FontRegistry) -> Result<Metadata, ConfigError> {
+        let defaults = Metadata::default();
+        let font = |field, name: Option<String>| {
+            name.map(|name| {
+                fonts
+                    .get(&name)
+                    .map_err(|e| ConfigError::Invalid(field, e.to_string()))
+            })
+            .transpose()
+        };
+        if let Some([0, _] | [_, 0]) = self.frame_size {
+            return Err(ConfigError::Invalid(
+                "frame_size",
+                "frames need a width and height".to_string(),
+            ));
+        }
+        if self.frames == Some(0) {
+            return Err(ConfigError::Invalid(
+                "frames",
+                "a sprite sheet needs at least 1 frame".to_string(),
+            ));
+        }
+        let ken_burns = match self.pan {
+            Some(path) => {
+                let defaults = KenBurnsSettings::default();
+                let zoom = self.zoom.map(|[start, end]| (start, end));
+                if let Some((start, end)) = zoom {
+                    in_range("zoom", Some(start), 1.0..=4.0)?;
+                    in_range("zoom", Some(end), 1.0..=4.0)?;
+                }
+                Some(KenBurnsSettings {
+                    path: path.parse().map_err(|e| ConfigError::Invalid("pan", e))?,
+                    period: in_range("pan_seconds", self.pan_seconds, 1..=3600)?
+                        .map_or(defaults.period, |seconds| {
+                            Duration::from_secs(seconds as u64)
+                        }),
+                    zoom: zoom.unwrap_or(defaults.zoom),
+                })
+            }
+            None if self.pan_seconds.is_some() || self.zoom.is_some() => {
+                return Err(ConfigError::Invalid(
+                    "pan",
+                    "pan_seconds and zoom need a pan path".to_string(),
+                ))
+            }
+            None => None,
+        };
+        let mut style = TextStyle::default();
+        if let Some(outline) = parse_color("outline", self.outline)? {
+            style = style.with_outline(outline);
+        }
+        if let Some(shadow) = parse_color("shadow", self.shadow)? {
+            style = style.with_shadow(shadow);
+        }
+        if self.frame_ms.contains(&0) {
+            return Err(ConfigError::Invalid(
+                "frame_ms",
+                "frames need to show for at least 1ms".to_string(),
+            ));
+        }
+
+        Ok(Metadata {
+            time: self.time.map_or(defaults.time, |[x, y]| Point::new(x, y)),
+            date: self.date.map_or(defaults.date, |[x, y]| Point::new(x, y)),
+            time_style: TextStyle {
+                color: parse_text_color("time_color", self.time_color)?
+                    .unwrap_or(defaults.time_style.color),
+                font: font("time_font", self.time_font)?.unwrap_or(defaults.time_style.font),
+                ..style.clone()
+            },
+            date_style: TextStyle {
+                color: parse_text_color("date_color", self.date_color)?
+                    .unwrap_or(defaults.date_style.color),
+                font: font("date_font", self.date_font)?.unwrap_or(defaults.date_style.font),
+                ..style
+            },
+            tags: self
+                .tags
+                .iter()
+                .map(|tag| tag.parse().map_err(|e| ConfigError::Invalid("tags", e)))
+                .collect::<Result<_, _>>()?,
+            animation: Animation {
+                frame_size: self
+                    .frame_size
+                    .map(|[width, height]| Size::new(width, height)),
+                frames: self.frames,
+                frame_delays: self
+                    .frame_ms
+                    .iter()
+                    .map(|&ms| Duration::from_millis(ms as u64))
+                    .collect(),
+                ken_burns,
+            },
+        })
+    }
+}
+
+pub struct Background {
+    pub image: Image,
+    pub metadata: Metadata,
+}
+
+impl Background {
+    pub fn builtin() -> Vec<Background> {
+        let fonts = FontRegistry::builtin();
+
+        BUNDLED
+            .iter()
+            .map(|(name, image, metadata)| Background {
+                image: Image::new(image),
+                metadata: Metadata::parse(
+                    &PathBuf::from(format!("assets/{}.toml", name)),
+                    metadata,
+                    &fonts,
+                )
+                .unwrap(),
+            })
+            .collect()
+    }
+
+    // A pack is a directory of images filling the panel, each optionally described by a TOML file.
+    // Fonts are picked by name from `fonts`, the built-in ones and those of the font directory.
+    pub fn load(dir: &Path, size: Size, fonts: &FontRegistry) -> Vec<Background> {
+        match list_dir(dir) {
+            Ok(paths) => paths
+                .into_iter()
+                .map(|path| {
+                    let metadata = Metadata::read(&path, fonts).unwrap_or_else(|e| {
+                        eprintln!("lumi-dash: ignoring metadata of {}: {}", path.display(), e);
+                        Metadata::default()
+                    });
+                    let image = Image::load(&path, size, &metadata.animation).unwrap_or_else(|e| {
+                        eprintln!("lumi-dash: {}", e);
+                        Image::error(size, &e)
+                    });
+                    Background { image, metadata }
+                })
+                .collect(),
+            Err(e) => {
+                eprintln!("lumi-dash: {}", e);
+                let bottom = size.height as i32 - 2;
+                vec![Background {
+                    image: Image::error(size, &e),
+                    metadata: Metadata {
+                        time: Point::new(2, bottom),
+                        date: Point::new(23, bottom),
+                        ..Default::default()
+                    },
+                }]
+            }
+        }
+    }
+}
+
+// Holiday backgrounds take over on their day, otherwise every background matching the time is
+// eligible. When none matches all of them are.
+pub fn eligible<'a, I>(metadata: I, now: NaiveDateTime) -> Vec<usize>
+where
+    I: IntoIterator<Item = &'a Metadata>,
+{
+    let metadata: Vec<&Metadata> = metadata.into_iter().collect();
+    let matching = |holiday: bool| -> Vec<usize> {
+        (0..metadata.len())
+            .filter(|&i| metadata[i].is_holiday() == holiday && metadata[i].matches(now))
+            .collect()
+    };
+
+    let holidays = matching(true);
+    if !holidays.is_empty() {
+        return holidays;
+    }
+    match matching(false) {
+        matching if matching.is_empty() => (0..metadata.len()).collect(),
+        matching => matching,
+    }
+}
+
+// Deals out the eligible backgrounds in random order, none repeats before all of them were shown.
+// A new round starts when the eligible set changes.
+#[derive(Default)]
+pub struct Shuffle {
+    eligible: Vec<usize>,
+    queue: Vec<usize>,
+}
+
+impl Shuffle {
+    pub fn next<R: Rng>(&mut self, eligible: Vec<usize>, current: usize, rng: &mut R) -> usize {
+        if eligible != self.eligible {
+            self.eligible = eligible;
+            self.queue.clear();
+        }
+        if self.queue.is_empty() {
+            self.queue = self.eligible.clone();
+            self.queue.shuffle(rng);
+            // The new round doesn't start with the background that is already showing.
+            if self.queue.len() > 1 && self.queue.last() == Some(&current) {
+                self.queue.swap(0, self.eligible.len() - 1);
+            }
+        }
+
+        self.queue.pop().unwrap_or(current)
+    }
+}
+
+// Colors are written like on the web, "#ffa500".
+fn parse_color(field: &'static str, value: Option<String>) -> Result<Option<Rgb888>, ConfigError> {
+    value
+        .map(|color| {
+            let hex = color
+                .strip_prefix('#')
+                .filter(|hex| hex.len() == 6)
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| {
+                    ConfigError::Invalid(field, format!("'{}' isn't a color like #ffa500", color))
+                })?;
+            Ok(Rgb888::new((hex >> 16) as u8, (hex >> 8) as u8, hex as u8))
+        })
+        .transpose()
+}
+
+// "auto" picks black or white, whichever stands out more against the background behind the text.
+fn parse_text_color(
+    field: &'static str,
+    value: Option<String>,
+) -> Result<Option<TextColor>, ConfigError> {
+    match value.as_deref() {
+        Some("auto") => Ok(Some(TextColor::Auto)),
+        _ => Ok(parse_color(field, value)?.map(TextColor::Fixed)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use chrono::{NaiveDate, NaiveDateTime};
+    use embedded_graphics::geometry::{Point, Size};
+    use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+    use image::{Rgb, RgbImage};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::{eligible, Background, Metadata, Shuffle, Tag};
+    use crate::config::ConfigError;
+    use crate::modules::ken_burns::{KenBurnsSettings, PanPath};
+    use crate::pixel_display::font::FontRegistry;
+    use crate::pixel_display::text_style::{TextColor, TextStyle};
+    use crate::test_util;
+
+    fn at(month: u32, day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, month, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    fn parse(contents: &str) -> Result<Metadata, ConfigError> {
+        Metadata::parse("test.toml".as_ref(), contents, &FontRegistry::builtin())
+    }
+
+    fn tagged(tags: &[&str]) -> Metadata {
+        Metadata {
+            tags: tags.iter().map(|tag| tag.parse().unwrap()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reads_the_metadata_next_to_each_image() {
+        let dir = test_util::temp_dir();
+        for name in ["aurora.png", "beach.png", "city.png"] {
+            RgbImage::from_pixel(64, 32, Rgb([0, 0, 0]))
+                .save(dir.join(name))
+                .unwrap();
+        }
+        fs::write(
+            dir.join("aurora.toml"),
+            r##"
+            time = [20, 28]
+            date = [43, 28]
+            time_color = "#ffa500"
+            date_color = "auto"
+            time_font = "10x20"
+            outline = "#000000"
+            tags = ["night", "winter"]
+            "##,
+        )
+        .unwrap();
+        fs::write(dir.join("city.toml"), r#"tags = ["rainy"]"#).unwrap();
+
+        let backgrounds = Background::load(&dir, Size::new(64, 32), &FontRegistry::builtin());
+
+        assert_eq!(backgrounds.len(), 3);
+        assert_eq!(
+            backgrounds[0].metadata,
+            Metadata {
+                time: Point::new(20, 28),
+                date: Point::new(43, 28),
+                time_style: TextStyle::new(Rgb888::new(255, 165, 0))
+                    .with_outline(Rgb888::BLACK)
+                    .with_font(FontRegistry::builtin().get("10x20").unwrap()),
+                date_style: TextStyle {
+                    color: TextColor::Auto,
+                    outline: Some(Rgb888::BLACK),
+                    ..Default::default()
+                },
+                tags: vec![Tag::Night, Tag::Winter],
+                ..Default::default()
+            }
+        );
+        assert_eq!(backgrounds[1].metadata, Metadata::default());
+        assert_eq!(backgrounds[2].metadata, Metadata::default());
+    }
+
+    #[test]
+    fn picks_backgrounds_for_the_time_of_day_and_season() {
+        let backgrounds = [
+            tagged(&["night"]),
+            tagged(&["morning", "day"]),
+            tagged(&["winter"]),
+            tagged(&["evening", "summer"]),
+            tagged(&[]),
+        ];
+
+        assert_eq!(eligible(&backgrounds, at(4, 5, 13)), [1, 4]);
+        assert_eq!(eligible(&backgrounds, at(1, 10, 23)), [0, 2, 4]);
+        assert_eq!(eligible(&backgrounds, at(7, 1, 19)), [3, 4]);
+    }
+
+    #[test]
+    fn holidays_take_over_on_their_day() {
+        let backgrounds = [
+            tagged(&["12-24", "12-25"]),
+            tagged(&["winter"]),
+            tagged(&[]),
+        ];
+
+        assert_eq!(eligible(&backgrounds, at(12, 25, 9)), [0]);
+        assert_eq!(eligible(&backgrounds, at(12, 26, 9)), [1, 2]);
+        assert_eq!(eligible(&backgrounds[..1], at(6, 1, 9)), [0]);
+    }
+
+    #[test]
+    fn shows_every_background_once_per_round() {
+        let mut shuffle = Shuffle::default();
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut current = 0;
+
+        let mut round: Vec<usize> = (0..4)
+            .map(|_| {
+                current = shuffle.next(vec![0, 1, 2, 3], current, &mut rng);
+                current
+            })
+            .collect();
+        let last = current;
+        let next = shuffle.next(vec![0, 1, 2, 3], current, &mut rng);
+        round.sort();
+
+        assert_eq!(round, [0, 1, 2, 3]);
+        assert_ne!(next, last);
+    }
+
+    #[test]
+    fn rejects_unknown_tags() {
+        assert_eq!("02-29".parse::<Tag>(), Ok(Tag::Date { month: 2, day: 29 }));
+        assert!("13-01".parse::<Tag>().is_err());
+        assert!("rainy".parse::<Tag>().is_err());
+    }
+
+    #[test]
+    fn reads_pan_and_zoom() {
+        let metadata = parse(
+            r#"
+            pan = "left-right"
+            pan_seconds = 90
+            zoom = [1.0, 1.5]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            metadata.animation.ken_burns,
+            Some(KenBurnsSettings {
+                path: PanPath::LeftToRight,
+                period: Duration::from_secs(90),
+                zoom: (1.0, 1.5),
+            })
+        );
+        assert!(parse("zoom = [1.0, 1.5]").is_err());
+        assert!(parse("pan = \"center\"\nzoom = [0.5, 1.0]").is_err());
+    }
+
+    #[test]
+    fn rejects_sprite_sheets_without_frames() {
+        let error = parse("frame_size = [16, 8]\nframes = 0").err().unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "invalid frames: a sprite sheet needs at least 1 frame"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fonts() {
+        let error = parse("date_font = \"comic-sans\"").err().unwrap();
+
+        assert!(matches!(error, ConfigError::Invalid("date_font", _)));
+    }
+
+    #[test]
+    fn rejects_malformed_colors() {
+        for color in ["ffa500", "#ffa50", "#orange"] {
+            let contents = format!("outline = \"{}\"", color);
+            assert!(parse(&contents).is_err());
+        }
+    }
+}
@@ -1,10 +1,45 @@
 use std::collections::VecDeque;
+use std::time::Duration;
 
-use super::{app::App, main_menu::MainMenu, spotify::Spotify};
-use crate::pixel_display::pixel_display::PixelDisplay;
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::pixelcolor::Rgb888;
+
+use super::{
+    app::App,
+    background::Background,
+    main_menu::MainMenu,
+    screen_saver::{ScreenSaver, ScreenState},
+    spotify::Spotify,
+    transition::{Direction, Transition, TransitionKind, TransitionSettings},
+};
+use crate::{
+    clock::Clock,
+    modules::{module::Module, time::Time},
+    pixel_display::{
+        brightness::BrightnessLevels, font::Fonts, pixel_display::PixelDisplay,
+        text_style::TextStyle,
+    },
+};
 use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+const IDLE_CLOCK_COLOR: Rgb888 = Rgb888::new(40, 40, 40);
+
 pub struct Launcher {
     apps: VecDeque<Box<dyn App>>,
+    dirty: bool,
+    clock: Arc<dyn Clock>,
+    screen_saver: ScreenSaver,
+    screen: ScreenState,
+    idle_clock: Time,
+    drawn_minute: Option<i64>,
+    transition_settings: TransitionSettings,
+    transition: Option<Transition>,
+    last_turn: Direction,
+    display_size: Size,
+    brightness_levels: BrightnessLevels,
+    // Picked with the brightness input, applied to the display when the next frame is drawn.
+    picked_brightness: Option<u8>,
 }
 
 pub enum Input {
@@ -12,36 +47,158 @@ pub enum Input {
     Prev,
     Pressed,
     Held,
+    // Steps through the brightness levels, in every app.
+    Brightness,
+    // Sent by apps rather than the user, so it doesn't wake the screen up.
+    SwitchApp,
 }
 
 impl Launcher {
-    pub fn new(input_tx: Sender<Input>) -> Self {
+    pub fn new(
+        input_tx: Sender<Input>,
+        clock: Arc<dyn Clock>,
+        mut screen_saver: ScreenSaver,
+        transition_settings: TransitionSettings,
+        backgrounds: Vec<Background>,
+        background_interval: Duration,
+        fonts: Fonts,
+    ) -> Self {
         let mut apps = VecDeque::<Box<dyn App>>::new();
-        let main: Box<MainMenu<'_>> = Box::new(MainMenu::new());
-        let spotify: Box<Spotify> = Box::new(Spotify::new(input_tx.clone()));
+        let main: Box<MainMenu> = Box::new(MainMenu::new(
+            clock.clone(),
+            backgrounds,
+            background_interval,
+        ));
+        let spotify: Box<Spotify> = Box::new(Spotify::new(input_tx.clone(), fonts.title));
 
         apps.push_back(main);
         apps.push_back(spotify);
 
         apps.front_mut().unwrap().enable();
+        screen_saver.wake(clock.instant());
+
+        Self {
+            apps,
+            dirty: true,
+            idle_clock: Time::new(clock.clone())
+                .with_style(TextStyle::new(IDLE_CLOCK_COLOR).with_font(fonts.clock)),
+            clock,
+            screen_saver,
+            screen: ScreenState::Awake,
+            drawn_minute: None,
+            transition_settings,
+            transition: None,
+            last_turn: Direction::Left,
+            display_size: Size::zero(),
+            brightness_levels: BrightnessLevels::default(),
+            picked_brightness: None,
+        }
+    }
 
-        Self { apps }
+    pub fn with_brightness_levels(self, brightness_levels: BrightnessLevels) -> Self {
+        Launcher {
+            brightness_levels,
+            ..self
+        }
     }
 
     pub fn draw(&mut self, display: &mut PixelDisplay) {
-        self.apps.front_mut().unwrap().draw(display);
+        self.display_size = display.size();
+        if let Some(level) = self.picked_brightness.take() {
+            display.set_brightness(level);
+        }
+
+        match self.screen {
+            ScreenState::Awake => match self.transition {
+                Some(ref mut transition) => {
+                    self.apps.front_mut().unwrap().draw(transition.incoming());
+                    transition.draw(self.clock.instant(), display);
+                }
+                None => self.apps.front_mut().unwrap().draw(display),
+            },
+            ScreenState::Clock => {
+                let bounds = self.idle_clock.bounding_box(Point::zero());
+                let free = display.size().saturating_sub(bounds.size) / 2;
+                let point = Point::new(free.width as i32, free.height as i32) - bounds.top_left;
+                self.idle_clock.draw(point, display);
+                self.drawn_minute = Some(self.clock.now().timestamp() / 60);
+            }
+            ScreenState::Off => (),
+        }
+        self.dirty = false;
+    }
+
+    pub fn needs_redraw(&mut self) -> bool {
+        let now = self.clock.now();
+        let screen = self.screen_saver.state(now, self.clock.instant());
+        if screen != self.screen {
+            self.screen = screen;
+            self.dirty = true;
+        }
+
+        match self.screen {
+            ScreenState::Awake => {
+                let app_needs_redraw = self.apps.front_mut().unwrap().needs_redraw();
+                let animating = self.update_transition();
+                self.dirty || app_needs_redraw || animating
+            }
+            ScreenState::Clock => self.dirty || self.drawn_minute != Some(now.timestamp() / 60),
+            ScreenState::Off => self.dirty,
+        }
     }
 
     pub fn handle_input(&mut self, input: Input) {
+        self.dirty = true;
+        if let Input::SwitchApp = input {
+            self.switch_app();
+            return;
+        }
+
+        self.screen_saver.wake(self.clock.instant());
+        // The input that wakes the screen up isn't passed on to the app.
+        if self.screen != ScreenState::Awake {
+            return;
+        }
+
         match input {
+            Input::Next => self.last_turn = Direction::Left,
+            Input::Prev => self.last_turn = Direction::Right,
             Input::Held => self.switch_app(),
+            Input::Brightness => {
+                self.picked_brightness = Some(self.brightness_levels.step());
+                return;
+            }
             _ => (),
         }
         self.apps.front_mut().unwrap().input(input);
     }
 
+    // Returns true while a transition is running, once it ends the app is drawn on its own again.
+    fn update_transition(&mut self) -> bool {
+        match self.transition {
+            Some(ref transition) if transition.is_finished(self.clock.instant()) => {
+                self.transition = None;
+                true
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
     fn switch_app(&mut self) {
         let mut old = self.apps.pop_front().unwrap();
+        // The outgoing app is rendered once more off-screen, its frame is animated out.
+        if self.transition_settings.kind != TransitionKind::Cut && self.display_size != Size::zero()
+        {
+            let mut outgoing = PixelDisplay::offscreen(self.display_size);
+            old.draw(&mut outgoing);
+            self.transition = Some(Transition::new(
+                self.transition_settings,
+                self.last_turn,
+                self.clock.instant(),
+                outgoing,
+            ));
+        }
         old.disable();
         self.apps.push_back(old);
         self.apps.front_mut().unwrap().enable();
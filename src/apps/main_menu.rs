@@ -1,86 +1,153 @@
-use embedded_graphics::geometry::Point;
-use rand::Rng;
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use std::sync::Arc;
 use std::time::Instant;
 
-use std::{collections::VecDeque, time::Duration};
+use std::time::Duration;
 
 use crate::{
-    modules::{date::Date, image::Image, module::Module, time::Time},
-    pixel_display::pixel_display::PixelDisplay,
+    clock::Clock,
+    modules::{date::Date, module::Module, time::Time},
+    pixel_display::{framebuffer::Framebuffer, pixel_display::PixelDisplay, surface::Surface},
 };
 
-use super::{app::App, launcher::Input};
+use super::{
+    app::App,
+    background::{eligible, Background, Shuffle},
+    launcher::Input,
+};
 
-const SAKURA: &[u8; 6282] = include_bytes!("../../assets/sakura.bmp");
-const CLOUD: &[u8; 1186] = include_bytes!("../../assets/cloud.bmp");
-const FOREST: &[u8; 1190] = include_bytes!("../../assets/forest.bmp");
-const NIGHT: &[u8; 6282] = include_bytes!("../../assets/night.bmp");
-const ART: &[u8; 6282] = include_bytes!("../../assets/art.bmp");
+const BACKGROUND_SIZE: Size = Size::new(64, 32);
 
-pub struct MainMenu<'a> {
-    time: Time,
-    date: Date,
+pub struct MainMenu {
+    clock: Arc<dyn Clock>,
     timer: Instant,
-    backgrounds: VecDeque<(Image<'a>, Point, Point)>,
+    rotation_interval: Duration,
+    drawn_minute: Option<i64>,
+    backgrounds: Vec<Background>,
+    current: usize,
+    // Animations are timed by the clock from when their background came up, not by frame count.
+    shown_at: Instant,
+    drawn_frame: Option<usize>,
+    shuffle: Shuffle,
+    // The background and the time and date on top of it are kept as layers between frames.
+    background_layer: Option<Framebuffer>,
+    overlay: Option<Framebuffer>,
 }
 
-impl<'a> MainMenu<'a> {
-    pub fn new() -> MainMenu<'a> {
-        let time: Time = Time {};
-        let date: Date = Date {};
-
-        let mut backgrounds: VecDeque<(Image<'a>, Point, Point)> = VecDeque::new();
-        backgrounds.push_back((Image::new(SAKURA), Point::new(2, 6), Point::new(23, 6)));
-        backgrounds.push_back((Image::new(CLOUD), Point::new(44, 5), Point::new(44, 11)));
-        backgrounds.push_back((Image::new(FOREST), Point::new(2, 30), Point::new(23, 30)));
-        backgrounds.push_back((Image::new(NIGHT), Point::new(20, 28), Point::new(43, 28)));
-        backgrounds.push_back((Image::new(ART), Point::new(20, 28), Point::new(43, 28)));
-
-        let timer = Instant::now();
+impl MainMenu {
+    pub fn new(
+        clock: Arc<dyn Clock>,
+        backgrounds: Vec<Background>,
+        rotation_interval: Duration,
+    ) -> MainMenu {
+        let timer = clock.instant();
 
         Self {
-            time,
-            date,
+            clock,
             timer,
+            rotation_interval,
+            drawn_minute: None,
             backgrounds,
+            current: 0,
+            shown_at: timer,
+            drawn_frame: None,
+            shuffle: Shuffle::default(),
+            background_layer: None,
+            overlay: None,
         }
     }
 
+    // Picks the next background that suits the time of day, season or holiday.
     fn randomize_background(&mut self) {
-        let num = rand::thread_rng().gen_range(1..self.backgrounds.len());
-        self.backgrounds.swap(0, num);
+        let now = self.clock.now().naive_local();
+        let eligible = eligible(self.backgrounds.iter().map(|b| &b.metadata), now);
+        self.current = self
+            .shuffle
+            .next(eligible, self.current, &mut rand::thread_rng());
+        self.shown_at = self.clock.instant();
+        self.invalidate_layers();
+    }
+
+    fn show(&mut self, index: usize) {
+        self.current = index % self.backgrounds.len();
+        self.shown_at = self.clock.instant();
+        self.invalidate_layers();
+    }
+
+    fn frame(&self) -> usize {
+        let elapsed = self.clock.instant() - self.shown_at;
+        self.backgrounds[self.current].image.frame_at(elapsed)
+    }
+
+    // The next background is picked when the frame is drawn.
+    fn rotation_due(&self) -> bool {
+        self.clock.instant() - self.timer > self.rotation_interval
+    }
+
+    fn invalidate_layers(&mut self) {
+        self.drawn_frame = None;
+        self.background_layer = None;
+        self.overlay = None;
     }
 }
 
-impl App for MainMenu<'_> {
+impl App for MainMenu {
     fn draw(&mut self, display: &mut PixelDisplay) {
-        let current = self.backgrounds.front().unwrap();
-        self.backgrounds
-            .front()
-            .unwrap()
-            .0
-            .draw(Point::new(0, 0), display);
-        self.time.draw(current.1, display);
-        self.date.draw(current.2, display);
-
-        if self.timer.elapsed().as_secs() > 2700 {
+        if self.rotation_due() {
             self.randomize_background();
-            self.timer = Instant::now();
+            self.timer = self.clock.instant();
+        }
+        let size = display.size();
+        let minute = self.clock.now().timestamp() / 60;
+        if self.background_layer.as_ref().map(|l| l.size()) != Some(size) {
+            self.invalidate_layers();
+        }
+        if self.drawn_minute != Some(minute) {
+            self.overlay = None;
         }
+        let frame = self.frame();
+
+        // Bundled backgrounds are made for one 64x32 panel, larger displays show them centered.
+        let current = &self.backgrounds[self.current];
+        let offset = size.saturating_sub(current.image.size().component_max(BACKGROUND_SIZE)) / 2;
+        let offset = Point::new(offset.width as i32, offset.height as i32);
+
+        // Animation frames are drawn over the previous one, the layer is kept for the background.
+        let background = self
+            .background_layer
+            .get_or_insert_with(|| Framebuffer::new(size));
+        if self.drawn_frame != Some(frame) {
+            current.image.draw_frame(frame, offset, background);
+        }
+        display.blit(background, Point::zero(), u8::MAX);
+
+        let overlay = self.overlay.get_or_insert_with(|| {
+            let meta = &current.metadata;
+            let time = Time::new(self.clock.clone()).with_style(meta.time_style.clone());
+            let date = Date::new(self.clock.clone()).with_style(meta.date_style.clone());
+            let mut layer = Framebuffer::over(background);
+            time.draw(meta.time + offset, &mut layer);
+            date.draw(meta.date + offset, &mut layer);
+            layer
+        });
+        display.blit(overlay, Point::zero(), u8::MAX);
+
+        self.drawn_minute = Some(minute);
+        self.drawn_frame = Some(frame);
+    }
+
+    fn needs_redraw(&mut self) -> bool {
+        self.rotation_due()
+            || self.drawn_minute != Some(self.clock.now().timestamp() / 60)
+            || self.drawn_frame != Some(self.frame())
     }
 
     fn input(&mut self, input: Input) {
         match input {
-            Input::Next => {
-                let old = self.backgrounds.pop_front().unwrap();
-                self.backgrounds.push_back(old);
-            }
-            Input::Prev => {
-                let old = self.backgrounds.pop_back().unwrap();
-                self.backgrounds.push_front(old);
-            }
+            Input::Next => self.show(self.current + 1),
+            Input::Prev => self.show(self.current + self.backgrounds.len() - 1),
             Input::Pressed => {}
-            Input::Held => {}
+            Input::Held | Input::SwitchApp | Input::Brightness => {}
         }
     }
 
@@ -88,7 +155,148 @@ impl App for MainMenu<'_> {
         self.randomize_background();
     }
 
-    fn disable(&mut self) {
-        ()
+    fn disable(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use embedded_graphics::geometry::{Point, Size};
+    use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+
+    use super::MainMenu;
+    use crate::apps::background::{Background, Tag};
+    use crate::apps::{app::App, launcher::Input};
+    use crate::clock::{Clock, FakeClock};
+    use crate::pixel_display::font::FontRegistry;
+    use crate::pixel_display::snapshot;
+    use crate::test_util;
+
+    const ROTATION_INTERVAL: Duration = Duration::from_secs(2700);
+
+    #[test]
+    fn draws_every_background() {
+        let clock = Arc::new(FakeClock::new(snapshot::fixed_instant()));
+        let mut main_menu = MainMenu::new(clock, Background::builtin(), ROTATION_INTERVAL);
+
+        for name in ["sakura", "cloud", "forest", "night", "art"] {
+            let mut display = snapshot::display();
+            main_menu.draw(&mut display);
+            snapshot::assert_snapshot(&format!("main_menu_{}", name), &display);
+            main_menu.input(Input::Next);
+        }
+    }
+
+    #[test]
+    fn rotates_background_after_interval() {
+        let clock = Arc::new(FakeClock::new(snapshot::fixed_instant()));
+        let mut main_menu = MainMenu::new(clock.clone(), Background::builtin(), ROTATION_INTERVAL);
+        let mut display = snapshot::display();
+        let start = clock.instant();
+
+        clock.advance(ROTATION_INTERVAL);
+        main_menu.draw(&mut display);
+        assert_eq!(main_menu.timer, start);
+
+        clock.advance(Duration::from_secs(1));
+        assert!(main_menu.needs_redraw());
+        assert!(main_menu.needs_redraw());
+        assert_eq!(main_menu.timer, start);
+
+        main_menu.draw(&mut display);
+        assert_eq!(main_menu.timer, clock.instant());
+        assert!(!main_menu.needs_redraw());
+    }
+
+    #[test]
+    fn picks_backgrounds_that_suit_the_time() {
+        let clock = Arc::new(FakeClock::new(snapshot::fixed_instant()));
+        let mut main_menu = MainMenu::new(clock, Background::builtin(), ROTATION_INTERVAL);
+        let night = main_menu
+            .backgrounds
+            .iter()
+            .position(|b| b.metadata.tags.contains(&Tag::Night))
+            .unwrap();
+
+        // A spring afternoon, the night background has to wait.
+        for _ in 0..20 {
+            main_menu.enable();
+            assert_ne!(main_menu.current, night);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_an_error_background() {
+        let clock = Arc::new(FakeClock::new(snapshot::fixed_instant()));
+        let backgrounds = Background::load(
+            "missing".as_ref(),
+            Size::new(64, 32),
+            &FontRegistry::builtin(),
+        );
+        assert_eq!(backgrounds.len(), 1);
+
+        let mut main_menu = MainMenu::new(clock, backgrounds, ROTATION_INTERVAL);
+        main_menu.enable();
+        main_menu.input(Input::Next);
+        let mut display = snapshot::display();
+        main_menu.draw(&mut display);
+
+        assert_eq!(
+            display.framebuffer().pixel(Point::zero()),
+            Some(Rgb888::RED)
+        );
+    }
+
+    #[test]
+    fn animates_at_the_pace_of_the_clock() {
+        let dir = test_util::temp_dir();
+        test_util::write_gif(
+            &dir.join("stars.gif"),
+            Size::new(64, 32),
+            &[(Rgb888::RED, 100), (Rgb888::BLUE, 100)],
+        );
+        let backgrounds = Background::load(&dir, Size::new(64, 32), &FontRegistry::builtin());
+
+        let clock = Arc::new(FakeClock::new(snapshot::fixed_instant()));
+        let mut main_menu = MainMenu::new(clock.clone(), backgrounds, ROTATION_INTERVAL);
+        let mut display = snapshot::display();
+        main_menu.draw(&mut display);
+        assert_eq!(
+            display.framebuffer().pixel(Point::new(40, 0)),
+            Some(Rgb888::RED)
+        );
+        let layer = |menu: &MainMenu| menu.background_layer.as_ref().unwrap().pixels().as_ptr();
+        let first_layer = layer(&main_menu);
+
+        clock.advance(Duration::from_millis(99));
+        assert!(!main_menu.needs_redraw());
+        clock.advance(Duration::from_millis(1));
+        assert!(main_menu.needs_redraw());
+
+        main_menu.draw(&mut display);
+        assert_eq!(
+            display.framebuffer().pixel(Point::new(40, 0)),
+            Some(Rgb888::BLUE)
+        );
+        assert_eq!(layer(&main_menu), first_layer);
+    }
+
+    #[test]
+    fn redraws_only_when_the_minute_changes() {
+        let clock = Arc::new(FakeClock::new(snapshot::fixed_instant()));
+        let mut main_menu = MainMenu::new(clock.clone(), Background::builtin(), ROTATION_INTERVAL);
+        let mut display = snapshot::display();
+
+        assert!(main_menu.needs_redraw());
+        main_menu.draw(&mut display);
+        assert!(!main_menu.needs_redraw());
+
+        clock.advance(Duration::from_secs(59));
+        assert!(!main_menu.needs_redraw());
+
+        clock.advance(Duration::from_secs(1));
+        assert!(main_menu.needs_redraw());
     }
 }
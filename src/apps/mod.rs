@@ -1,4 +1,7 @@
 pub mod app;
+pub mod background;
 pub mod launcher;
 pub mod main_menu;
+pub mod screen_saver;
 pub mod spotify;
+pub mod transition;
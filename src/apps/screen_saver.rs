@@ -0,0 +1,132 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, NaiveTime};
+
+// How long an input keeps the screen on during quiet hours when no idle timeout is configured.
+const QUIET_WAKE_DURATION: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScreenState {
+    Awake,
+    Clock,
+    Off,
+}
+
+pub struct ScreenSaver {
+    idle_timeout: Option<Duration>,
+    idle_clock: bool,
+    quiet_hours: Option<(NaiveTime, NaiveTime)>,
+    last_input: Option<Instant>,
+}
+
+impl Default for ScreenSaver {
+    fn default() -> Self {
+        ScreenSaver::new(None, false, None)
+    }
+}
+
+impl ScreenSaver {
+    pub fn new(
+        idle_timeout: Option<Duration>,
+        idle_clock: bool,
+        quiet_hours: Option<(NaiveTime, NaiveTime)>,
+    ) -> Self {
+        ScreenSaver {
+            idle_timeout,
+            idle_clock,
+            quiet_hours,
+            last_input: None,
+        }
+    }
+
+    pub fn wake(&mut self, instant: Instant) {
+        self.last_input = Some(instant);
+    }
+
+    pub fn state(&self, now: DateTime<Local>, instant: Instant) -> ScreenState {
+        let idle_for = self.last_input.map_or(Duration::MAX, |last| {
+            instant.saturating_duration_since(last)
+        });
+
+        if self.in_quiet_hours(now.time()) {
+            if idle_for < self.idle_timeout.unwrap_or(QUIET_WAKE_DURATION) {
+                return ScreenState::Awake;
+            }
+            return ScreenState::Off;
+        }
+
+        match self.idle_timeout {
+            Some(timeout) if idle_for >= timeout && self.idle_clock => ScreenState::Clock,
+            Some(timeout) if idle_for >= timeout => ScreenState::Off,
+            _ => ScreenState::Awake,
+        }
+    }
+
+    fn in_quiet_hours(&self, time: NaiveTime) -> bool {
+        match self.quiet_hours {
+            Some((start, end)) if start <= end => start <= time && time < end,
+            Some((start, end)) => time >= start || time < end,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use chrono::{DateTime, Local, NaiveTime, TimeZone};
+
+    use super::{ScreenSaver, ScreenState};
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 4, 5, hour, minute, 0).unwrap()
+    }
+
+    fn quiet_hours() -> Option<(NaiveTime, NaiveTime)> {
+        Some((
+            NaiveTime::from_hms_opt(23, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(6, 30, 0).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn falls_back_to_the_clock_after_the_idle_timeout() {
+        let mut saver = ScreenSaver::new(Some(Duration::from_secs(300)), true, None);
+        let start = Instant::now();
+        saver.wake(start);
+
+        assert_eq!(saver.state(at(12, 0), start), ScreenState::Awake);
+        let later = start + Duration::from_secs(300);
+        assert_eq!(saver.state(at(12, 5), later), ScreenState::Clock);
+
+        saver.wake(later);
+        assert_eq!(saver.state(at(12, 5), later), ScreenState::Awake);
+    }
+
+    #[test]
+    fn stays_on_without_an_idle_timeout() {
+        let mut saver = ScreenSaver::default();
+        let start = Instant::now();
+        saver.wake(start);
+
+        let later = start + Duration::from_secs(86_400);
+        assert_eq!(saver.state(at(3, 0), later), ScreenState::Awake);
+    }
+
+    #[test]
+    fn turns_off_during_quiet_hours_until_woken() {
+        let mut saver = ScreenSaver::new(None, true, quiet_hours());
+        let start = Instant::now();
+        saver.wake(start);
+
+        let night = start + Duration::from_secs(3600);
+        assert_eq!(saver.state(at(2, 0), night), ScreenState::Off);
+        assert_eq!(saver.state(at(22, 59), night), ScreenState::Awake);
+
+        saver.wake(night);
+        assert_eq!(saver.state(at(2, 0), night), ScreenState::Awake);
+        let after_wake = night + Duration::from_secs(60);
+        assert_eq!(saver.state(at(2, 1), after_wake), ScreenState::Off);
+    }
+}
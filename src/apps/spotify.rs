@@ -1,34 +1,47 @@
-use std::{path::PathBuf, sync::RwLock, thread::sleep, time::Instant};
+use std::{sync::RwLock, thread::sleep, time::Instant};
 
 use chrono::Duration;
 use embedded_graphics::{
-    geometry::Point,
+    geometry::{OriginDimensions, Point, Size},
     pixelcolor::{Rgb888, WebColors},
-    primitives::{Line, PrimitiveStyle, PrimitiveStyleBuilder, Triangle},
+    primitives::{Line, PrimitiveStyle, PrimitiveStyleBuilder, Rectangle, Triangle},
 };
-use image::{codecs::bmp::BmpEncoder, EncodableLayout};
+use image::DynamicImage;
 use rspotify::{
     clients::OAuthClient,
-    model::{AdditionalType, Country, FullTrack, Market},
-    scopes, AuthCodeSpotify, Config, Credentials, OAuth, Token,
+    model::{AdditionalType, Country, FullTrack, Id, Market},
+    scopes, AuthCodeSpotify, Config, Credentials, OAuth,
 };
 use std::sync::mpsc;
 use std::thread;
 
 use super::{app::App, launcher::Input};
 use crate::{
-    modules::{image::Image, module::Module},
-    pixel_display::pixel_display::PixelDisplay,
+    modules::{
+        image::fit,
+        image_cache::{ImageCache, ImageKey},
+    },
+    pixel_display::{
+        font::Font,
+        framebuffer::Framebuffer,
+        pixel_display::PixelDisplay,
+        surface::Surface,
+        text_layout::{Alignment, Overflow, TextBox},
+        text_style::TextStyle,
+    },
 };
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
-use std::sync::Mutex;
+
+const COVER_CACHE_SIZE: usize = 16;
+const COVER_SIZE: u32 = 32;
 
 pub struct Spotify {
     sender: Sender<Input>,
     data: Arc<RwLock<SpotifyData>>,
     prev_data: Option<SpotifyData>,
     is_rendering: Arc<RwLock<bool>>,
+    title_style: TextStyle,
 }
 
 pub struct SpotifyClient {
@@ -38,20 +51,21 @@ pub struct SpotifyClient {
     market: Market,
     current_song: Option<FullTrack>,
     paused: bool,
+    covers: ImageCache,
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct SpotifyData {
     current_song: Option<FullTrack>,
     duration: Option<Duration>,
     progress: Option<Duration>,
     device_id: Option<String>,
-    cover_raw: Option<Vec<u8>>,
+    cover: Option<Arc<Framebuffer>>,
     paused: bool,
 }
 
 impl Spotify {
-    pub fn new(input_tx: Sender<Input>) -> Self {
+    pub fn new(input_tx: Sender<Input>, title_font: Font) -> Self {
         let creds = Credentials::from_env().unwrap();
 
         let oauth = OAuth {
@@ -65,28 +79,12 @@ impl Spotify {
             ..Default::default()
         };
 
-        let token: Option<Token> =
-            match Token::from_cache(PathBuf::from(".spotify_token_cache.json")) {
-                Ok(e) => {
-                    if !e.is_expired() {
-                        Some(e);
-                    }
-                    None
-                }
-                Err(_) => None,
-            };
-
-        let spotifyapi;
-        match token {
-            Some(t) => spotifyapi = AuthCodeSpotify::from_token(t),
-            None => {
-                spotifyapi = AuthCodeSpotify::with_config(creds, oauth, config);
-                let url = spotifyapi.get_authorize_url(false).unwrap();
-                spotifyapi
-                    .prompt_for_token(&url)
-                    .expect("couldn't authenticate successfully");
-            }
-        }
+        // The cached token is used, and refreshed, when there is one, otherwise this asks to log in.
+        let spotifyapi = AuthCodeSpotify::with_config(creds, oauth, config);
+        let url = spotifyapi.get_authorize_url(false).unwrap();
+        spotifyapi
+            .prompt_for_token(&url)
+            .expect("couldn't authenticate successfully");
 
         let market = Market::Country(Country::Netherlands);
         let mut client = SpotifyClient {
@@ -96,6 +94,7 @@ impl Spotify {
             spotify: spotifyapi,
             market,
             paused: false,
+            covers: ImageCache::new(COVER_CACHE_SIZE),
         };
 
         let data = Arc::new(RwLock::new(SpotifyData {
@@ -104,7 +103,7 @@ impl Spotify {
             progress: None,
             device_id: None,
             paused: true,
-            cover_raw: None,
+            cover: None,
         }));
 
         let (tx, rx) = mpsc::channel();
@@ -114,6 +113,7 @@ impl Spotify {
             data: data.clone(),
             prev_data: None,
             is_rendering: Arc::new(RwLock::new(false)),
+            title_style: TextStyle::default().with_font(title_font),
         };
 
         let is_rendering = spotify.is_rendering.clone();
@@ -122,14 +122,13 @@ impl Spotify {
             let input_tx = input_tx.clone();
             let mut elapsed = Instant::now();
             loop {
-                match rx.try_recv() {
-                    Ok(input) => match input {
+                if let Ok(input) = rx.try_recv() {
+                    match input {
                         Input::Next => client.next_track(),
                         Input::Prev => client.previous_track(),
                         Input::Pressed => client.toggle_playback(),
-                        Input::Held => (),
-                    },
-                    Err(_) => (),
+                        Input::Held | Input::SwitchApp | Input::Brightness => (),
+                    }
                 }
                 sleep(std::time::Duration::from_secs(1));
                 if elapsed.elapsed().as_secs() > 2 {
@@ -139,21 +138,19 @@ impl Spotify {
                         && new.current_song.is_some()
                         && !*is_rendering.read().unwrap()
                     {
-                        input_tx.send(Input::Held);
+                        input_tx.send(Input::SwitchApp).ok();
                     }
                     if data.current_song.is_some()
                         && new.current_song.is_none()
                         && *is_rendering.read().unwrap()
                     {
-                        input_tx.send(Input::Held);
+                        input_tx.send(Input::SwitchApp).ok();
                     }
+                    data.cover = new.cover;
                     data.current_song = new.current_song;
                     data.duration = new.duration;
                     data.progress = new.progress;
                     data.paused = new.paused;
-                    if new.cover_raw.is_some() {
-                        data.cover_raw = new.cover_raw;
-                    }
                     drop(data);
 
                     elapsed = Instant::now()
@@ -172,7 +169,7 @@ impl SpotifyClient {
         let mut progress = None;
         let mut device_id = None;
         let mut paused = true;
-        let mut cover_raw = None;
+        let mut cover = None;
 
         match self
             .spotify
@@ -181,21 +178,7 @@ impl SpotifyClient {
             Ok(e) => match e {
                 Some(playing) => match playing.item.unwrap() {
                     rspotify::model::PlayableItem::Track(e) => {
-                        match &self.current_song {
-                            Some(s) => {
-                                if s.name != e.name {
-                                    cover_raw = Some(Self::download_cover(
-                                        e.album.images.first().unwrap().url.clone(),
-                                    ));
-                                }
-                                duration = Some(s.duration);
-                            }
-                            None => {
-                                cover_raw = Some(Self::download_cover(
-                                    e.album.images.first().unwrap().url.clone(),
-                                ));
-                            }
-                        }
+                        cover = Self::cover(&mut self.covers, &e);
                         duration = Some(e.duration);
                         current_song = Some(e.clone());
                         self.current_song = Some(e.clone());
@@ -215,25 +198,22 @@ impl SpotifyClient {
             Err(e) => println!("An error has occurred: {}", e),
         }
 
-        match self
+        if let Ok(p) = self
             .spotify
             .current_playback(Some(self.market), Some(&[AdditionalType::Track]))
         {
-            Ok(p) => {
-                progress = match p {
-                    Some(ref playbackcontext) => playbackcontext.progress,
-                    None => None,
-                };
-                self.device_id = match p {
-                    Some(ref playbackcontext) => playbackcontext.device.id.clone(),
-                    None => None,
-                };
-                device_id = match p {
-                    Some(ref playbackcontext) => playbackcontext.device.id.clone(),
-                    None => None,
-                }
+            progress = match p {
+                Some(ref playbackcontext) => playbackcontext.progress,
+                None => None,
+            };
+            self.device_id = match p {
+                Some(ref playbackcontext) => playbackcontext.device.id.clone(),
+                None => None,
+            };
+            device_id = match p {
+                Some(ref playbackcontext) => playbackcontext.device.id.clone(),
+                None => None,
             }
-            Err(_) => (),
         }
 
         self.last_update = Instant::now();
@@ -244,23 +224,41 @@ impl SpotifyClient {
             progress,
             device_id,
             paused,
-            cover_raw,
+            cover,
         }
     }
 
-    fn download_cover(imgurl: String) -> Vec<u8> {
-        let resp = ureq::get(&imgurl).call().ok().unwrap();
-        let mut bytes: Vec<u8> = Vec::new();
-        resp.into_reader().read_to_end(&mut bytes).unwrap();
-        let mut image = image::load_from_memory(&bytes).unwrap();
-        image = image.resize_to_fill(32, 32, image::imageops::FilterType::Lanczos3);
-        let mut bmpraw = Vec::new();
-        let mut bmpencoder = BmpEncoder::<Vec<u8>>::new(&mut bmpraw);
-        bmpencoder
-            .encode(&image.as_bytes(), 32, 32, image.color())
-            .unwrap();
+    // Looked up on every update, so covers are downloaded once per album, or once per image URL
+    // for tracks without an album id. Songs without a cover, or one that couldn't be loaded, are
+    // drawn without it, a cover that failed isn't tried again.
+    fn cover(covers: &mut ImageCache, track: &FullTrack) -> Option<Arc<Framebuffer>> {
+        let url = &track.album.images.first()?.url;
+        let key = match track.album.id {
+            Some(ref id) => ImageKey::Album(id.id().to_string()),
+            None => ImageKey::Url(url.clone()),
+        };
+
+        covers.get_or_insert_with(key, || match Self::load_cover(url) {
+            Ok(cover) => Some(cover),
+            Err(e) => {
+                eprintln!("lumi-dash: couldn't load the cover {}: {}", url, e);
+                None
+            }
+        })
+    }
+
+    fn load_cover(url: &str) -> Result<Framebuffer, Box<dyn std::error::Error>> {
+        let mut bytes = Vec::new();
+        ureq::get(url)
+            .call()?
+            .into_reader()
+            .read_to_end(&mut bytes)?;
 
-        bmpraw
+        Ok(Self::scale_cover(image::load_from_memory(&bytes)?))
+    }
+
+    fn scale_cover(image: DynamicImage) -> Framebuffer {
+        fit(&image, Size::new(COVER_SIZE, COVER_SIZE))
     }
 
     fn next_track(&mut self) {
@@ -287,41 +285,107 @@ impl SpotifyClient {
     }
 }
 
+// The cover goes left of the title, progress bar and play indicator, or above them on displays
+// taller than wide. `info` is the area next to the cover they are laid out in.
+struct Layout {
+    cover: Point,
+    info: Rectangle,
+}
+
+impl Layout {
+    fn new(size: Size) -> Self {
+        let cover = COVER_SIZE as i32;
+        let (width, height) = (size.width as i32, size.height as i32);
+
+        match width >= height {
+            true => {
+                let top = height / 2 - cover / 2;
+                Layout {
+                    cover: Point::new(0, top),
+                    info: Rectangle::new(
+                        Point::new(cover, top),
+                        Size::new(size.width.saturating_sub(COVER_SIZE), COVER_SIZE),
+                    ),
+                }
+            }
+            false => {
+                let top = height / 2 - cover;
+                Layout {
+                    cover: Point::new(width / 2 - cover / 2, top),
+                    info: Rectangle::new(
+                        Point::new(0, top + cover),
+                        Size::new(size.width, COVER_SIZE),
+                    ),
+                }
+            }
+        }
+    }
+
+    fn title(&self) -> Rectangle {
+        Rectangle::new(
+            self.info.top_left + Point::new(1, 0),
+            Size::new(self.info.size.width.saturating_sub(2), 12),
+        )
+    }
+
+    // The left and right end of the progress bar.
+    fn bar(&self) -> (Point, Point) {
+        let Point { x, y } = self.info.top_left;
+        let right = x + self.info.size.width as i32 - 5;
+
+        (Point::new(x + 4, y + 17), Point::new(right, y + 17))
+    }
+
+    // The top center of the play indicator, under the middle of the progress bar.
+    fn indicator(&self) -> Point {
+        let (start, end) = self.bar();
+
+        Point::new((start.x + end.x) / 2, self.info.top_left.y + 22)
+    }
+}
+
 impl SpotifyData {
-    fn draw_progress_bar(&self, display: &mut PixelDisplay) {
-        let duration = Line {
-            start: Point::new(36, 17),
-            end: Point::new(59, 17),
+    fn draw_progress_bar(&self, layout: &Layout, display: &mut PixelDisplay) {
+        let (start, end) = layout.bar();
+        // Without the progress or a duration there is nothing to show.
+        let ratio = match (self.progress, self.duration) {
+            (Some(progress), Some(duration)) if duration.num_seconds() > 0 => {
+                progress.num_seconds() as f32 / duration.num_seconds() as f32
+            }
+            _ => return,
         };
+        if end.x <= start.x {
+            return;
+        }
 
+        let duration = Line { start, end };
         let style = PrimitiveStyle::with_stroke(Rgb888::CSS_DARK_GRAY, 1);
 
         display.draw_line(duration, style);
-        let max_seconds = self.duration.unwrap().num_seconds() as f32;
-        let current_seconds = self.progress.unwrap().num_seconds() as f32;
-        let ratio: f32 = current_seconds / max_seconds;
-        let adjusted_progress = ((ratio * (59.0 - 36.0)) + 36.0).round() as i32;
+        let length = (ratio.clamp(0.0, 1.0) * (end.x - start.x) as f32).round() as i32;
         let progress = Line {
-            start: Point::new(36, 17),
-            end: Point::new(adjusted_progress.try_into().unwrap(), 17),
+            start,
+            end: start + Point::new(length, 0),
         };
         let style = PrimitiveStyle::with_stroke(Rgb888::CSS_WHITE, 1);
 
         display.draw_line(progress, style)
     }
 
-    fn draw_playing_indicator(&self, display: &mut PixelDisplay) {
+    fn draw_playing_indicator(&self, layout: &Layout, display: &mut PixelDisplay) {
+        let Point { x: center, y: top } = layout.indicator();
+
         match self.paused {
             false => {
                 let style = PrimitiveStyle::with_stroke(Rgb888::CSS_LIME_GREEN, 2);
 
                 let left = Line {
-                    start: Point::new(45, 22),
-                    end: Point::new(45, 28),
+                    start: Point::new(center - 2, top),
+                    end: Point::new(center - 2, top + 6),
                 };
                 let right = Line {
-                    start: Point::new(48, 22),
-                    end: Point::new(48, 28),
+                    start: Point::new(center + 1, top),
+                    end: Point::new(center + 1, top + 6),
                 };
                 display.draw_line(left, style);
                 display.draw_line(right, style);
@@ -333,9 +397,9 @@ impl SpotifyData {
                     .fill_color(Rgb888::CSS_LIME_GREEN)
                     .build();
 
-                let p1 = Point::new(44, 22);
-                let p2 = Point::new(44, 28);
-                let p3 = Point::new(51, 25);
+                let p1 = Point::new(center - 3, top);
+                let p2 = Point::new(center - 3, top + 6);
+                let p3 = Point::new(center + 4, top + 3);
 
                 let triangle = Triangle {
                     vertices: [p1, p2, p3],
@@ -351,15 +415,26 @@ impl App for Spotify {
     fn draw(&mut self, display: &mut PixelDisplay) {
         match self.data.try_read() {
             Ok(d) => {
-                d.draw(display);
+                d.draw(display, &self.title_style);
                 self.prev_data = Some(d.clone());
             }
-            Err(d) => self.prev_data.clone().unwrap().draw(display),
+            Err(_) => self
+                .prev_data
+                .clone()
+                .unwrap()
+                .draw(display, &self.title_style),
+        }
+    }
+
+    fn needs_redraw(&mut self) -> bool {
+        match self.data.try_read() {
+            Ok(d) => self.prev_data.as_ref() != Some(&*d),
+            Err(_) => false,
         }
     }
 
     fn input(&mut self, input: Input) {
-        self.sender.send(input);
+        self.sender.send(input).ok();
     }
 
     fn enable(&mut self) {
@@ -374,21 +449,141 @@ impl App for Spotify {
 }
 
 impl SpotifyData {
-    fn draw(&self, display: &mut PixelDisplay) {
+    fn draw(&self, display: &mut PixelDisplay, title_style: &TextStyle) {
+        let layout = Layout::new(display.size());
+
         match &self.current_song {
             Some(t) => {
                 // TODO: Text scrolling & Album name
-                display.draw_text(&t.name, Point::new(33, 10));
-                self.draw_progress_bar(display);
-                if self.cover_raw.is_some() {
-                    let bytes = &self.cover_raw.clone().unwrap().to_vec();
-                    let image = Image::new(bytes);
-                    image.draw(Point::new(0, 0), display);
+                let title = TextBox::new(layout.title())
+                    .with_alignment(Alignment::Start, Alignment::End)
+                    .with_max_lines(2)
+                    .with_overflow(Overflow::Wrap);
+                display.draw_text_box(&t.name, &title, title_style);
+                self.draw_progress_bar(&layout, display);
+                if let Some(ref cover) = self.cover {
+                    display.blit(cover, layout.cover, u8::MAX);
                 }
             }
-            None => display.draw_text(&String::from("Nothing playing"), Point::new(2, 16)),
+            None => {
+                let panel = TextBox::new(Rectangle::new(Point::zero(), display.size()))
+                    .with_alignment(Alignment::Center, Alignment::Center)
+                    .with_overflow(Overflow::Wrap);
+                display.draw_text_box("Nothing playing", &panel, &TextStyle::default());
+            }
         }
 
-        self.draw_playing_indicator(display);
+        self.draw_playing_indicator(&layout, display);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::Duration;
+    use embedded_graphics::geometry::Point;
+    use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+    use image::{DynamicImage, Rgb, RgbImage};
+    use rspotify::model::FullTrack;
+
+    use super::{SpotifyClient, SpotifyData};
+    use crate::config::PanelConfig;
+    use crate::pixel_display::orientation::{Orientation, Rotation};
+    use crate::pixel_display::snapshot;
+    use crate::pixel_display::surface::Surface;
+    use crate::pixel_display::text_style::TextStyle;
+
+    fn fixture(paused: bool) -> SpotifyData {
+        let track: FullTrack =
+            serde_json::from_str(include_str!("../../tests/fixtures/spotify_track.json")).unwrap();
+        let cover = RgbImage::from_fn(32, 32, |x, y| Rgb([(x * 8) as u8, (y * 8) as u8, 160]));
+
+        SpotifyData {
+            duration: Some(track.duration),
+            progress: Some(Duration::seconds(83)),
+            current_song: Some(track),
+            device_id: None,
+            cover: Some(Arc::new(SpotifyClient::scale_cover(
+                DynamicImage::ImageRgb8(cover),
+            ))),
+            paused,
+        }
+    }
+
+    #[test]
+    fn draws_playing_track() {
+        let mut display = snapshot::display();
+        fixture(false).draw(&mut display, &TextStyle::default());
+        snapshot::assert_snapshot("spotify_playing", &display);
+    }
+
+    #[test]
+    fn draws_paused_track() {
+        let mut display = snapshot::display();
+        fixture(true).draw(&mut display, &TextStyle::default());
+        snapshot::assert_snapshot("spotify_paused", &display);
+    }
+
+    #[test]
+    fn keeps_long_titles_on_the_panel() {
+        let mut display = snapshot::display();
+        let mut data = fixture(false);
+        data.current_song.as_mut().unwrap().name =
+            "Night Drive Through The Neon Lights (Extended Mix)".to_string();
+        data.draw(&mut display, &TextStyle::default());
+        snapshot::assert_snapshot("spotify_long_title", &display);
+    }
+
+    #[test]
+    fn lays_out_for_chained_panels() {
+        let mut display = snapshot::display_for(&PanelConfig {
+            chain_length: 2,
+            ..Default::default()
+        });
+        fixture(false).draw(&mut display, &TextStyle::default());
+        snapshot::assert_snapshot("spotify_chained", &display);
+    }
+
+    #[test]
+    fn lays_out_for_portrait_panels() {
+        let mut display = snapshot::display_for(&PanelConfig {
+            orientation: Orientation {
+                rotation: Rotation::Clockwise90,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        fixture(false).draw(&mut display, &TextStyle::default());
+        snapshot::assert_snapshot("spotify_portrait", &display);
+    }
+
+    #[test]
+    fn skips_the_progress_bar_without_a_duration() {
+        let mut display = snapshot::display();
+        let mut data = fixture(false);
+        data.duration = Some(Duration::zero());
+        data.draw(&mut display, &TextStyle::default());
+        assert_eq!(display.pixel(Point::new(36, 17)), Some(Rgb888::BLACK));
+
+        data.duration = None;
+        data.progress = None;
+        data.draw(&mut display, &TextStyle::default());
+        assert_eq!(display.pixel(Point::new(36, 17)), Some(Rgb888::BLACK));
+    }
+
+    #[test]
+    fn draws_nothing_playing() {
+        let mut display = snapshot::display();
+        let data = SpotifyData {
+            current_song: None,
+            duration: None,
+            progress: None,
+            device_id: None,
+            cover: None,
+            paused: true,
+        };
+        data.draw(&mut display, &TextStyle::default());
+        snapshot::assert_snapshot("spotify_nothing_playing", &display);
     }
 }
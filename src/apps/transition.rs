@@ -0,0 +1,208 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+use embedded_graphics::prelude::*;
+
+use crate::pixel_display::framebuffer::Framebuffer;
+use crate::pixel_display::pixel_display::PixelDisplay;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TransitionKind {
+    Cut,
+    Slide,
+    Crossfade,
+    Wipe,
+}
+
+impl FromStr for TransitionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cut" | "none" => Ok(TransitionKind::Cut),
+            "slide" => Ok(TransitionKind::Slide),
+            "crossfade" => Ok(TransitionKind::Crossfade),
+            "wipe" => Ok(TransitionKind::Wipe),
+            _ => Err(format!(
+                "unknown transition '{}', expected cut, slide, crossfade or wipe",
+                s
+            )),
+        }
+    }
+}
+
+// The way the incoming app moves in, follows the last turn of the encoder.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy)]
+pub struct TransitionSettings {
+    pub kind: TransitionKind,
+    pub duration: Duration,
+}
+
+impl Default for TransitionSettings {
+    fn default() -> Self {
+        TransitionSettings {
+            kind: TransitionKind::Slide,
+            duration: Duration::from_millis(300),
+        }
+    }
+}
+
+// Composes the last frame of the outgoing app with live frames of the incoming app.
+pub struct Transition {
+    settings: TransitionSettings,
+    direction: Direction,
+    started: Instant,
+    outgoing: PixelDisplay,
+    incoming: PixelDisplay,
+}
+
+impl Transition {
+    pub fn new(
+        settings: TransitionSettings,
+        direction: Direction,
+        started: Instant,
+        outgoing: PixelDisplay,
+    ) -> Self {
+        let incoming = PixelDisplay::offscreen(outgoing.size());
+
+        Transition {
+            settings,
+            direction,
+            started,
+            outgoing,
+            incoming,
+        }
+    }
+
+    pub fn is_finished(&self, instant: Instant) -> bool {
+        instant - self.started >= self.settings.duration
+    }
+
+    // The off-screen display the incoming app draws its next frame into.
+    pub fn incoming(&mut self) -> &mut PixelDisplay {
+        self.incoming.update();
+        &mut self.incoming
+    }
+
+    pub fn draw(&self, instant: Instant, display: &mut PixelDisplay) {
+        let elapsed = (instant - self.started).as_secs_f32();
+        let progress = ease(elapsed / self.settings.duration.as_secs_f32());
+        let from = self.outgoing.framebuffer();
+        let to = self.incoming.framebuffer();
+
+        let area = display.bounding_box();
+        let colors = area.points().map(|point| {
+            compose(
+                self.settings.kind,
+                self.direction,
+                progress,
+                from,
+                to,
+                point,
+            )
+        });
+        display.fill_contiguous(&area, colors).ok();
+    }
+}
+
+fn ease(progress: f32) -> f32 {
+    let p = progress.clamp(0.0, 1.0);
+    p * p * (3.0 - 2.0 * p)
+}
+
+fn compose(
+    kind: TransitionKind,
+    direction: Direction,
+    progress: f32,
+    from: &Framebuffer,
+    to: &Framebuffer,
+    point: Point,
+) -> Rgb888 {
+    let width = from.size().width as i32;
+    let color =
+        |frame: &Framebuffer, x: i32| frame.pixel(Point::new(x, point.y)).unwrap_or(Rgb888::BLACK);
+    let shift = (progress * width as f32).round() as i32;
+
+    match (kind, direction) {
+        (TransitionKind::Cut, _) => color(to, point.x),
+        (TransitionKind::Slide, Direction::Left) => match point.x + shift {
+            x if x < width => color(from, x),
+            x => color(to, x - width),
+        },
+        (TransitionKind::Slide, Direction::Right) => match point.x - shift {
+            x if x >= 0 => color(from, x),
+            x => color(to, x + width),
+        },
+        (TransitionKind::Crossfade, _) => {
+            let (a, b) = (color(from, point.x), color(to, point.x));
+            let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * progress).round() as u8;
+            Rgb888::new(mix(a.r(), b.r()), mix(a.g(), b.g()), mix(a.b(), b.b()))
+        }
+        (TransitionKind::Wipe, Direction::Left) if point.x >= width - shift => color(to, point.x),
+        (TransitionKind::Wipe, Direction::Right) if point.x < shift => color(to, point.x),
+        (TransitionKind::Wipe, _) => color(from, point.x),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+    use embedded_graphics::prelude::*;
+
+    use super::{compose, Direction, TransitionKind};
+    use crate::pixel_display::framebuffer::Framebuffer;
+
+    fn frames() -> (Framebuffer, Framebuffer) {
+        let mut from = Framebuffer::new(Size::new(4, 1));
+        let mut to = Framebuffer::new(Size::new(4, 1));
+        from.clear(Rgb888::RED).unwrap();
+        to.clear(Rgb888::BLUE).unwrap();
+        (from, to)
+    }
+
+    fn row(kind: TransitionKind, direction: Direction, progress: f32) -> Vec<Rgb888> {
+        let (from, to) = frames();
+        (0..4)
+            .map(|x| compose(kind, direction, progress, &from, &to, Point::new(x, 0)))
+            .collect()
+    }
+
+    #[test]
+    fn slides_in_the_direction_of_the_encoder() {
+        let (r, b) = (Rgb888::RED, Rgb888::BLUE);
+
+        assert_eq!(
+            row(TransitionKind::Slide, Direction::Left, 0.25),
+            [r, r, r, b]
+        );
+        assert_eq!(
+            row(TransitionKind::Slide, Direction::Right, 0.25),
+            [b, r, r, r]
+        );
+        assert_eq!(
+            row(TransitionKind::Slide, Direction::Left, 1.0),
+            [b, b, b, b]
+        );
+    }
+
+    #[test]
+    fn wipes_and_crossfades() {
+        let (r, b) = (Rgb888::RED, Rgb888::BLUE);
+
+        assert_eq!(
+            row(TransitionKind::Wipe, Direction::Right, 0.5),
+            [b, b, r, r]
+        );
+        assert_eq!(
+            row(TransitionKind::Crossfade, Direction::Left, 0.5)[0],
+            Rgb888::new(128, 0, 128)
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::Instant;
+
+use chrono::{DateTime, Local};
+
+#[cfg(test)]
+use std::{sync::Mutex, time::Duration};
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+    fn instant(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// A clock that stands still until it is advanced, so time based behaviour can be tested.
+#[cfg(test)]
+pub struct FakeClock {
+    state: Mutex<(DateTime<Local>, Instant)>,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new(now: DateTime<Local>) -> Self {
+        FakeClock {
+            state: Mutex::new((now, Instant::now())),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        *state = (
+            state.0 + chrono::Duration::from_std(duration).unwrap(),
+            state.1 + duration,
+        );
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Local> {
+        self.state.lock().unwrap().0
+    }
+
+    fn instant(&self) -> Instant {
+        self.state.lock().unwrap().1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{Local, TimeZone, Timelike};
+
+    use super::{Clock, FakeClock};
+
+    #[test]
+    fn fake_clock_is_frozen_until_advanced() {
+        let clock = FakeClock::new(Local.with_ymd_and_hms(2024, 4, 5, 13, 37, 0).unwrap());
+        let instant = clock.instant();
+
+        assert_eq!(clock.now().minute(), 37);
+        assert_eq!(clock.instant(), instant);
+
+        clock.advance(Duration::from_secs(90));
+
+        assert_eq!(clock.now().minute(), 38);
+        assert_eq!(clock.now().second(), 30);
+        assert_eq!(clock.instant() - instant, Duration::from_secs(90));
+    }
+}
@@ -0,0 +1,1014 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use std::time::Duration;
+
+use argh::FromArgs;
+use chrono::NaiveTime;
+use embedded_graphics::geometry::Size;
+use rpi_led_panel::{HardwareMapping, MultiplexMapperType, RGBMatrixConfig, RowAddressSetterType};
+use serde::Deserialize;
+
+use crate::apps::background::BackgroundSettings;
+use crate::apps::screen_saver::ScreenSaver;
+use crate::apps::transition::TransitionSettings;
+use crate::pixel_display::brightness::{
+    BrightnessLevels, BrightnessSchedule, BrightnessSettings, DEFAULT_LEVELS,
+};
+use crate::pixel_display::color::Calibration;
+use crate::pixel_display::font::{FontRegistry, Fonts};
+use crate::pixel_display::night_shift::{NightSchedule, NightShift};
+use crate::pixel_display::orientation::{Orientation, Rotation};
+use crate::pixel_display::power_limit::{PowerLimiter, PowerSettings, DEFAULT_MILLIAMPS_PER_LED};
+
+// The names rpi-led-panel accepts, its own errors don't list them.
+const HARDWARE_MAPPINGS: &str =
+    "AdafruitHat, AdafruitHatPwm, Regular, RegularPi1, Classic or ClassicPi1";
+const MULTIPLEXINGS: &str = "Stripe, Checkered, Spiral, ZStripe08, ZStripe44, ZStripe80, Coreman, \
+     Kaler2Scan, P10Z, QiangLiQ8, InversedZStripe, P10Outdoor1R1G1B1, P10Outdoor1R1G1B2, \
+     P10Outdoor1R1G1B3, P10Coreman, P8Outdoor1R1G1B, FlippedStripe or P10Outdoor32x16HalfScan";
+const ROW_SETTERS: &str = "Direct, ShiftRegister, DirectABCDLine, ABCShiftRegister or SM5266";
+
+#[derive(FromArgs)]
+/// Show the time, date and your current Spotify song on an LED matrix panel.
+pub struct Args {
+    /// path to a TOML config file, flags take precedence over its values
+    #[argh(option)]
+    pub config: Option<PathBuf>,
+
+    /// preview the dashboard in the terminal instead of on the panel or simulator
+    #[argh(switch)]
+    pub terminal: bool,
+
+    /// frames per second the main loop aims for, defaults to 30
+    #[argh(option, default = "30", from_str_fn(parse_fps))]
+    pub fps: u32,
+
+    /// panel brightness in percent, overrides the schedule until its next entry
+    #[argh(option)]
+    pub brightness: Option<u8>,
+
+    /// brightness levels by time of day, for example "07:00=100,22:00=15"
+    #[argh(option)]
+    pub brightness_schedule: Option<String>,
+
+    /// rows of a single panel, defaults to 32
+    #[argh(option)]
+    pub rows: Option<u32>,
+
+    /// columns of a single panel, defaults to 64
+    #[argh(option)]
+    pub cols: Option<u32>,
+
+    /// number of panels daisy-chained on one output, defaults to 1
+    #[argh(option)]
+    pub chain_length: Option<u32>,
+
+    /// number of parallel chains (1 to 3), defaults to 1
+    #[argh(option)]
+    pub parallel: Option<u32>,
+
+    /// GPIO mapping of the HAT or wiring, for example "AdafruitHat"
+    #[argh(option)]
+    pub hardware_mapping: Option<String>,
+
+    /// GPIO slowdown for faster Pis (0 to 4)
+    #[argh(option)]
+    pub slowdown: Option<u32>,
+
+    /// bits used for PWM, lower values refresh faster (1 to 11)
+    #[argh(option)]
+    pub pwm_bits: Option<u32>,
+
+    /// duration of the least significant PWM bit in nanoseconds (50 to 3000)
+    #[argh(option)]
+    pub pwm_lsb_nanoseconds: Option<u32>,
+
+    /// multiplexing scheme of outdoor panels, for example "Stripe"
+    #[argh(option)]
+    pub multiplexing: Option<String>,
+
+    /// row address type of the panel, for example "Direct"
+    #[argh(option)]
+    pub row_setter: Option<String>,
+
+    /// upper limit of the panel refresh rate in Hz
+    #[argh(option)]
+    pub refresh_rate: Option<u32>,
+
+    /// clockwise rotation of the output in degrees (0, 90, 180 or 270)
+    #[argh(option)]
+    pub rotation: Option<u32>,
+
+    /// mirror the output horizontally, true or false
+    #[argh(option)]
+    pub flip_horizontal: Option<bool>,
+
+    /// mirror the output vertically, true or false
+    #[argh(option)]
+    pub flip_vertical: Option<bool>,
+
+    /// gamma of all color channels, defaults to 1.0
+    #[argh(option)]
+    pub gamma: Option<f32>,
+
+    /// current the panel may draw in amps, brighter frames are dimmed to stay below it
+    #[argh(option)]
+    pub power_budget: Option<f32>,
+
+    /// minutes without input before the screen is blanked
+    #[argh(option)]
+    pub idle_minutes: Option<u32>,
+
+    /// animation when switching apps: slide, crossfade, wipe or cut
+    #[argh(option)]
+    pub transition: Option<String>,
+
+    /// directory with PNG, JPEG, GIF or BMP images to use as backgrounds
+    #[argh(option)]
+    pub backgrounds: Option<PathBuf>,
+
+    /// minutes between background changes in the main menu, defaults to 45
+    #[argh(option)]
+    pub background_minutes: Option<u32>,
+}
+
+impl Args {
+    fn panel_options(&self) -> PanelOptions {
+        PanelOptions {
+            rows: self.rows,
+            cols: self.cols,
+            chain_length: self.chain_length,
+            parallel: self.parallel,
+            hardware_mapping: self.hardware_mapping.clone(),
+            slowdown: self.slowdown,
+            pwm_bits: self.pwm_bits,
+            pwm_lsb_nanoseconds: self.pwm_lsb_nanoseconds,
+            multiplexing: self.multiplexing.clone(),
+            row_setter: self.row_setter.clone(),
+            refresh_rate: self.refresh_rate,
+            rotation: self.rotation,
+            flip_horizontal: self.flip_horizontal,
+            flip_vertical: self.flip_vertical,
+        }
+    }
+
+    fn brightness_options(&self) -> BrightnessOptions {
+        BrightnessOptions {
+            level: self.brightness,
+            schedule: self.brightness_schedule.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn screen_options(&self) -> ScreenOptions {
+        ScreenOptions {
+            idle_minutes: self.idle_minutes,
+            ..Default::default()
+        }
+    }
+
+    fn transition_options(&self) -> TransitionOptions {
+        TransitionOptions {
+            kind: self.transition.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn background_options(&self) -> BackgroundOptions {
+        BackgroundOptions {
+            directory: self.backgrounds.clone(),
+            rotation_minutes: self.background_minutes,
+        }
+    }
+
+    fn power_options(&self) -> PowerOptions {
+        PowerOptions {
+            budget_amps: self.power_budget,
+            ..Default::default()
+        }
+    }
+
+    fn color_options(&self) -> ColorOptions {
+        ColorOptions {
+            gamma: self.gamma,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(&'static str, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "couldn't read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "invalid config {}: {}", path.display(), e),
+            ConfigError::Invalid(field, message) => write!(f, "invalid {}: {}", field, message),
+        }
+    }
+}
+
+pub struct Config {
+    pub panel: PanelConfig,
+    pub calibration: Calibration,
+    pub brightness: BrightnessSettings,
+    pub night_shift: NightShift,
+    pub power: PowerSettings,
+    pub screen_saver: ScreenSaver,
+    pub transition: TransitionSettings,
+    pub backgrounds: BackgroundSettings,
+    pub fonts: Fonts,
+}
+
+impl Config {
+    pub fn load(args: &Args) -> Result<Config, ConfigError> {
+        let file = match args.config {
+            Some(ref path) => ConfigFile::read(path)?,
+            None => ConfigFile::default(),
+        };
+
+        Ok(Config {
+            panel: file.panel.merge(args.panel_options()).validate()?,
+            calibration: file.color.merge(args.color_options()).validate()?,
+            brightness: file
+                .brightness
+                .merge(args.brightness_options())
+                .validate()?,
+            night_shift: file.night.validate()?,
+            power: file.power.merge(args.power_options()).validate()?,
+            screen_saver: file.screen.merge(args.screen_options()).validate()?,
+            transition: file
+                .transition
+                .merge(args.transition_options())
+                .validate()?,
+            backgrounds: file
+                .backgrounds
+                .merge(args.background_options())
+                .validate()?,
+            fonts: file.fonts.validate()?,
+        })
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    panel: PanelOptions,
+    color: ColorOptions,
+    brightness: BrightnessOptions,
+    night: NightOptions,
+    power: PowerOptions,
+    screen: ScreenOptions,
+    transition: TransitionOptions,
+    backgrounds: BackgroundOptions,
+    fonts: FontOptions,
+}
+
+impl ConfigFile {
+    fn read(path: &Path) -> Result<ConfigFile, ConfigError> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+        toml::from_str(&contents).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+    }
+}
+
+// Panel settings as they come from the config file or flags, before validation.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct PanelOptions {
+    rows: Option<u32>,
+    cols: Option<u32>,
+    chain_length: Option<u32>,
+    parallel: Option<u32>,
+    hardware_mapping: Option<String>,
+    slowdown: Option<u32>,
+    pwm_bits: Option<u32>,
+    pwm_lsb_nanoseconds: Option<u32>,
+    multiplexing: Option<String>,
+    row_setter: Option<String>,
+    refresh_rate: Option<u32>,
+    rotation: Option<u32>,
+    flip_horizontal: Option<bool>,
+    flip_vertical: Option<bool>,
+}
+
+impl PanelOptions {
+    fn merge(self, overrides: PanelOptions) -> PanelOptions {
+        PanelOptions {
+            rows: overrides.rows.or(self.rows),
+            cols: overrides.cols.or(self.cols),
+            chain_length: overrides.chain_length.or(self.chain_length),
+            parallel: overrides.parallel.or(self.parallel),
+            hardware_mapping: overrides.hardware_mapping.or(self.hardware_mapping),
+            slowdown: overrides.slowdown.or(self.slowdown),
+            pwm_bits: overrides.pwm_bits.or(self.pwm_bits),
+            pwm_lsb_nanoseconds: overrides.pwm_lsb_nanoseconds.or(self.pwm_lsb_nanoseconds),
+            multiplexing: overrides.multiplexing.or(self.multiplexing),
+            row_setter: overrides.row_setter.or(self.row_setter),
+            refresh_rate: overrides.refresh_rate.or(self.refresh_rate),
+            rotation: overrides.rotation.or(self.rotation),
+            flip_horizontal: overrides.flip_horizontal.or(self.flip_horizontal),
+            flip_vertical: overrides.flip_vertical.or(self.flip_vertical),
+        }
+    }
+
+    fn validate(self) -> Result<PanelConfig, ConfigError> {
+        let defaults = PanelConfig::default();
+        let rotation = match self.rotation {
+            Some(degrees) => Rotation::from_degrees(degrees).ok_or_else(|| {
+                ConfigError::Invalid(
+                    "rotation",
+                    format!("{} isn't one of 0, 90, 180 or 270", degrees),
+                )
+            })?,
+            None => Rotation::None,
+        };
+
+        Ok(PanelConfig {
+            rows: in_range("rows", self.rows, 8..=64)?.unwrap_or(defaults.rows),
+            cols: in_range("cols", self.cols, 8..=256)?.unwrap_or(defaults.cols),
+            chain_length: in_range("chain_length", self.chain_length, 1..=16)?
+                .unwrap_or(defaults.chain_length),
+            parallel: in_range("parallel", self.parallel, 1..=3)?.unwrap_or(defaults.parallel),
+            hardware_mapping: parse_name(
+                "hardware_mapping",
+                self.hardware_mapping,
+                HARDWARE_MAPPINGS,
+            )?,
+            slowdown: in_range("slowdown", self.slowdown, 0..=4)?,
+            pwm_bits: in_range("pwm_bits", self.pwm_bits, 1..=11)?,
+            pwm_lsb_nanoseconds: in_range(
+                "pwm_lsb_nanoseconds",
+                self.pwm_lsb_nanoseconds,
+                50..=3000,
+            )?,
+            multiplexing: parse_name("multiplexing", self.multiplexing, MULTIPLEXINGS)?,
+            row_setter: parse_name("row_setter", self.row_setter, ROW_SETTERS)?,
+            refresh_rate: in_range("refresh_rate", self.refresh_rate, 1..=1000)?,
+            orientation: Orientation {
+                rotation,
+                flip_horizontal: self.flip_horizontal.unwrap_or(false),
+                flip_vertical: self.flip_vertical.unwrap_or(false),
+            },
+        })
+    }
+}
+
+// Gamma and white balance of the panel, each channel falls back to the shared `gamma`.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ColorOptions {
+    gamma: Option<f32>,
+    red_gamma: Option<f32>,
+    green_gamma: Option<f32>,
+    blue_gamma: Option<f32>,
+    red_gain: Option<f32>,
+    green_gain: Option<f32>,
+    blue_gain: Option<f32>,
+}
+
+impl ColorOptions {
+    fn merge(self, overrides: ColorOptions) -> ColorOptions {
+        ColorOptions {
+            gamma: overrides.gamma.or(self.gamma),
+            red_gamma: overrides.red_gamma.or(self.red_gamma),
+            green_gamma: overrides.green_gamma.or(self.green_gamma),
+            blue_gamma: overrides.blue_gamma.or(self.blue_gamma),
+            red_gain: overrides.red_gain.or(self.red_gain),
+            green_gain: overrides.green_gain.or(self.green_gain),
+            blue_gain: overrides.blue_gain.or(self.blue_gain),
+        }
+    }
+
+    fn validate(self) -> Result<Calibration, ConfigError> {
+        let gamma = in_range("gamma", self.gamma, 0.5..=4.0)?.unwrap_or(1.0);
+        let channel_gamma =
+            |field, value| in_range(field, value, 0.5..=4.0).map(|value| value.unwrap_or(gamma));
+        let gain = |field, value| in_range(field, value, 0.0..=1.0).map(|v| v.unwrap_or(1.0));
+
+        Ok(Calibration::new(
+            [
+                channel_gamma("red_gamma", self.red_gamma)?,
+                channel_gamma("green_gamma", self.green_gamma)?,
+                channel_gamma("blue_gamma", self.blue_gamma)?,
+            ],
+            [
+                gain("red_gain", self.red_gain)?,
+                gain("green_gain", self.green_gain)?,
+                gain("blue_gain", self.blue_gain)?,
+            ],
+        ))
+    }
+}
+
+// Without a schedule the panel stays at full brightness unless `level` is set.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct BrightnessOptions {
+    level: Option<u8>,
+    schedule: Option<String>,
+    levels: Option<Vec<u8>>,
+}
+
+impl BrightnessOptions {
+    fn merge(self, overrides: BrightnessOptions) -> BrightnessOptions {
+        BrightnessOptions {
+            level: overrides.level.or(self.level),
+            schedule: overrides.schedule.or(self.schedule),
+            levels: overrides.levels.or(self.levels),
+        }
+    }
+
+    fn validate(self) -> Result<BrightnessSettings, ConfigError> {
+        let schedule = match self.schedule {
+            Some(schedule) => schedule
+                .parse()
+                .map_err(|e| ConfigError::Invalid("schedule", e))?,
+            None => BrightnessSchedule::default(),
+        };
+        let levels = self.levels.unwrap_or_else(|| DEFAULT_LEVELS.to_vec());
+        if levels.is_empty() {
+            return Err(ConfigError::Invalid(
+                "levels",
+                "expected at least one level".to_string(),
+            ));
+        }
+        for &level in &levels {
+            in_range("levels", Some(level), 0..=100)?;
+        }
+
+        Ok(BrightnessSettings {
+            level: in_range("level", self.level, 0..=100)?,
+            schedule,
+            levels: BrightnessLevels::new(levels),
+        })
+    }
+}
+
+// Night shift follows either fixed times or the sun at a location, it's off without both.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct NightOptions {
+    start: Option<String>,
+    end: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    temperature: Option<u32>,
+    transition_minutes: Option<u32>,
+}
+
+impl NightOptions {
+    fn validate(self) -> Result<NightShift, ConfigError> {
+        let schedule = match (self.start, self.end, self.latitude, self.longitude) {
+            (None, None, None, None) => return Ok(NightShift::default()),
+            (Some(start), Some(end), None, None) => NightSchedule::Fixed {
+                start: parse_time("night start", &start)?,
+                end: parse_time("night end", &end)?,
+            },
+            (None, None, Some(latitude), Some(longitude)) => NightSchedule::Sun {
+                latitude: in_range("latitude", Some(latitude), -90.0..=90.0)?.unwrap(),
+                longitude: in_range("longitude", Some(longitude), -180.0..=180.0)?.unwrap(),
+            },
+            _ => {
+                return Err(ConfigError::Invalid(
+                    "night",
+                    "expected either start and end or latitude and longitude".to_string(),
+                ))
+            }
+        };
+        let temperature = in_range("temperature", self.temperature, 1000..=6500)?.unwrap_or(3000);
+        let transition =
+            in_range("transition_minutes", self.transition_minutes, 0..=240)?.unwrap_or(30);
+
+        Ok(NightShift::new(
+            schedule,
+            temperature,
+            Duration::from_secs(transition as u64 * 60),
+        ))
+    }
+}
+
+// Without a budget the current is only estimated, never limited.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct PowerOptions {
+    budget_amps: Option<f32>,
+    milliamps_per_led: Option<f32>,
+    stats_file: Option<PathBuf>,
+}
+
+impl PowerOptions {
+    fn merge(self, overrides: PowerOptions) -> PowerOptions {
+        PowerOptions {
+            budget_amps: overrides.budget_amps.or(self.budget_amps),
+            milliamps_per_led: overrides.milliamps_per_led.or(self.milliamps_per_led),
+            stats_file: overrides.stats_file.or(self.stats_file),
+        }
+    }
+
+    fn validate(self) -> Result<PowerSettings, ConfigError> {
+        Ok(PowerSettings {
+            limiter: PowerLimiter::new(
+                in_range("budget_amps", self.budget_amps, 0.1..=100.0)?,
+                in_range("milliamps_per_led", self.milliamps_per_led, 0.01..=100.0)?
+                    .unwrap_or(DEFAULT_MILLIAMPS_PER_LED),
+            ),
+            stats_file: self.stats_file,
+        })
+    }
+}
+
+// Idle blanking and quiet hours, the screen stays on when neither is configured.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ScreenOptions {
+    idle_minutes: Option<u32>,
+    idle_clock: Option<bool>,
+    quiet_start: Option<String>,
+    quiet_end: Option<String>,
+}
+
+impl ScreenOptions {
+    fn merge(self, overrides: ScreenOptions) -> ScreenOptions {
+        ScreenOptions {
+            idle_minutes: overrides.idle_minutes.or(self.idle_minutes),
+            idle_clock: overrides.idle_clock.or(self.idle_clock),
+            quiet_start: overrides.quiet_start.or(self.quiet_start),
+            quiet_end: overrides.quiet_end.or(self.quiet_end),
+        }
+    }
+
+    fn validate(self) -> Result<ScreenSaver, ConfigError> {
+        let idle_timeout = in_range("idle_minutes", self.idle_minutes, 1..=1440)?
+            .map(|minutes| Duration::from_secs(minutes as u64 * 60));
+        let quiet_hours = match (self.quiet_start, self.quiet_end) {
+            (Some(start), Some(end)) => Some((
+                parse_time("quiet_start", &start)?,
+                parse_time("quiet_end", &end)?,
+            )),
+            (None, None) => None,
+            _ => {
+                return Err(ConfigError::Invalid(
+                    "quiet hours",
+                    "expected both quiet_start and quiet_end".to_string(),
+                ))
+            }
+        };
+
+        Ok(ScreenSaver::new(
+            idle_timeout,
+            self.idle_clock.unwrap_or(false),
+            quiet_hours,
+        ))
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct TransitionOptions {
+    kind: Option<String>,
+    duration_ms: Option<u32>,
+}
+
+impl TransitionOptions {
+    fn merge(self, overrides: TransitionOptions) -> TransitionOptions {
+        TransitionOptions {
+            kind: overrides.kind.or(self.kind),
+            duration_ms: overrides.duration_ms.or(self.duration_ms),
+        }
+    }
+
+    fn validate(self) -> Result<TransitionSettings, ConfigError> {
+        let defaults = TransitionSettings::default();
+
+        Ok(TransitionSettings {
+            kind: match self.kind {
+                Some(kind) => kind
+                    .parse()
+                    .map_err(|e| ConfigError::Invalid("transition", e))?,
+                None => defaults.kind,
+            },
+            duration: in_range("duration_ms", self.duration_ms, 50..=2000)?
+                .map_or(defaults.duration, |ms| Duration::from_millis(ms as u64)),
+        })
+    }
+}
+
+// Without a directory the bundled backgrounds are shown.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct BackgroundOptions {
+    directory: Option<PathBuf>,
+    rotation_minutes: Option<u32>,
+}
+
+impl BackgroundOptions {
+    fn merge(self, overrides: BackgroundOptions) -> BackgroundOptions {
+        BackgroundOptions {
+            directory: overrides.directory.or(self.directory),
+            rotation_minutes: overrides.rotation_minutes.or(self.rotation_minutes),
+        }
+    }
+
+    fn validate(self) -> Result<BackgroundSettings, ConfigError> {
+        let defaults = BackgroundSettings::default();
+
+        Ok(BackgroundSettings {
+            directory: self.directory,
+            interval: in_range("rotation_minutes", self.rotation_minutes, 1..=1440)?
+                .map_or(defaults.interval, |minutes| {
+                    Duration::from_secs(minutes as u64 * 60)
+                }),
+        })
+    }
+}
+
+// Fonts are picked by name, BDF files in `directory` are named after their file.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FontOptions {
+    directory: Option<PathBuf>,
+    clock: Option<String>,
+    title: Option<String>,
+}
+
+impl FontOptions {
+    fn validate(self) -> Result<Fonts, ConfigError> {
+        let defaults = Fonts::default();
+        let mut registry = FontRegistry::builtin();
+        if let Some(ref dir) = self.directory {
+            registry
+                .load_dir(dir)
+                .map_err(|e| ConfigError::Invalid("fonts", e.to_string()))?;
+        }
+        let font = |field, name: Option<String>| {
+            name.map(|name| {
+                registry
+                    .get(&name)
+                    .map_err(|e| ConfigError::Invalid(field, e.to_string()))
+            })
+            .transpose()
+        };
+
+        Ok(Fonts {
+            clock: font("clock", self.clock)?.unwrap_or(defaults.clock),
+            title: font("title", self.title)?.unwrap_or(defaults.title),
+            registry,
+        })
+    }
+}
+
+// The hardware settings are only read to drive the panel.
+#[cfg_attr(not(feature = "real"), allow(dead_code))]
+pub struct PanelConfig {
+    pub rows: u32,
+    pub cols: u32,
+    pub chain_length: u32,
+    pub parallel: u32,
+    pub hardware_mapping: Option<HardwareMapping>,
+    pub slowdown: Option<u32>,
+    pub pwm_bits: Option<u32>,
+    pub pwm_lsb_nanoseconds: Option<u32>,
+    pub multiplexing: Option<MultiplexMapperType>,
+    pub row_setter: Option<RowAddressSetterType>,
+    pub refresh_rate: Option<u32>,
+    pub orientation: Orientation,
+}
+
+impl Default for PanelConfig {
+    fn default() -> Self {
+        PanelConfig {
+            rows: 32,
+            cols: 64,
+            chain_length: 1,
+            parallel: 1,
+            hardware_mapping: None,
+            slowdown: None,
+            pwm_bits: None,
+            pwm_lsb_nanoseconds: None,
+            multiplexing: None,
+            row_setter: None,
+            refresh_rate: None,
+            orientation: Orientation::default(),
+        }
+    }
+}
+
+impl PanelConfig {
+    // The physical size, chained panels extend it to the right and parallel chains downwards.
+    pub fn size(&self) -> Size {
+        Size::new(self.cols * self.chain_length, self.rows * self.parallel)
+    }
+
+    // The size apps draw at, rotated by a quarter turn the width and height swap.
+    pub fn logical_size(&self) -> Size {
+        self.orientation.logical_size(self.size())
+    }
+
+    // Settings that aren't configured keep the defaults of rpi-led-panel.
+    #[cfg_attr(not(feature = "real"), allow(dead_code))]
+    pub fn matrix_config(&self) -> RGBMatrixConfig {
+        let defaults = RGBMatrixConfig::default();
+
+        RGBMatrixConfig {
+            hardware_mapping: self.hardware_mapping.unwrap_or(defaults.hardware_mapping),
+            rows: self.rows as usize,
+            cols: self.cols as usize,
+            chain_length: self.chain_length as usize,
+            parallel: self.parallel as usize,
+            slowdown: self.slowdown.or(defaults.slowdown),
+            pwm_bits: self
+                .pwm_bits
+                .map_or(defaults.pwm_bits, |bits| bits as usize),
+            pwm_lsb_nanoseconds: self
+                .pwm_lsb_nanoseconds
+                .unwrap_or(defaults.pwm_lsb_nanoseconds),
+            multiplexing: self.multiplexing.or(defaults.multiplexing),
+            row_setter: self.row_setter.unwrap_or(defaults.row_setter),
+            refresh_rate: self
+                .refresh_rate
+                .map_or(defaults.refresh_rate, |rate| rate as usize),
+            ..defaults
+        }
+    }
+}
+
+pub(crate) fn in_range<T: PartialOrd + fmt::Display>(
+    field: &'static str,
+    value: Option<T>,
+    range: std::ops::RangeInclusive<T>,
+) -> Result<Option<T>, ConfigError> {
+    match value {
+        Some(v) if !range.contains(&v) => Err(ConfigError::Invalid(
+            field,
+            format!(
+                "{} is out of range, expected {} to {}",
+                v,
+                range.start(),
+                range.end()
+            ),
+        )),
+        _ => Ok(value),
+    }
+}
+
+fn parse_name<T: std::str::FromStr>(
+    field: &'static str,
+    value: Option<String>,
+    expected: &str,
+) -> Result<Option<T>, ConfigError> {
+    value
+        .map(|name| {
+            name.parse::<T>().map_err(|_| {
+                ConfigError::Invalid(
+                    field,
+                    format!("unknown value '{}', expected {}", name, expected),
+                )
+            })
+        })
+        .transpose()
+}
+
+fn parse_time(field: &'static str, value: &str) -> Result<NaiveTime, ConfigError> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .map_err(|_| ConfigError::Invalid(field, format!("'{}' isn't a time like 21:30", value)))
+}
+
+fn parse_fps(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(fps) if fps > 0 => Ok(fps),
+        _ => Err(format!("fps must be a positive number, got '{}'", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use argh::FromArgs;
+    use embedded_graphics::pixelcolor::Rgb888;
+
+    use super::{Args, ConfigError, ConfigFile};
+    use crate::pixel_display::font::Font;
+
+    fn args(flags: &[&str]) -> Args {
+        Args::from_args(&["lumi-dash"], flags).unwrap()
+    }
+
+    #[test]
+    fn flags_override_the_config_file() {
+        let file: ConfigFile = toml::from_str(
+            r#"
+            [panel]
+            rows = 64
+            chain_length = 2
+            pwm_bits = 7
+            "#,
+        )
+        .unwrap();
+
+        let panel = file
+            .panel
+            .merge(args(&["--chain-length", "3"]).panel_options())
+            .validate()
+            .unwrap();
+
+        assert_eq!(panel.rows, 64);
+        assert_eq!(panel.cols, 64);
+        assert_eq!(panel.chain_length, 3);
+        assert_eq!(panel.pwm_bits, Some(7));
+        assert_eq!(panel.matrix_config().pwm_bits, 7);
+    }
+
+    #[test]
+    fn flags_turn_flips_off_too() {
+        let file: ConfigFile = toml::from_str(
+            r#"
+            [panel]
+            flip_horizontal = true
+            flip_vertical = true
+            "#,
+        )
+        .unwrap();
+
+        let panel = file
+            .panel
+            .merge(args(&["--flip-horizontal", "false"]).panel_options())
+            .validate()
+            .unwrap();
+
+        assert!(!panel.orientation.flip_horizontal);
+        assert!(panel.orientation.flip_vertical);
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        let error = args(&["--pwm-bits", "12"])
+            .panel_options()
+            .validate()
+            .err()
+            .unwrap();
+
+        assert!(matches!(error, ConfigError::Invalid("pwm_bits", _)));
+        assert_eq!(
+            error.to_string(),
+            "invalid pwm_bits: 12 is out of range, expected 1 to 11"
+        );
+    }
+
+    #[test]
+    fn lists_the_hardware_mappings_it_accepts() {
+        let error = args(&["--hardware-mapping", "AdafruitBonnet"])
+            .panel_options()
+            .validate()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "invalid hardware_mapping: unknown value 'AdafruitBonnet', expected AdafruitHat, \
+             AdafruitHatPwm, Regular, RegularPi1, Classic or ClassicPi1"
+        );
+    }
+
+    #[test]
+    fn reads_the_brightness() {
+        let file: ConfigFile = toml::from_str(
+            r#"
+            [brightness]
+            schedule = "07:00=100,22:00=15"
+            levels = [100, 20]
+            "#,
+        )
+        .unwrap();
+
+        let mut brightness = file
+            .brightness
+            .merge(args(&["--brightness", "80"]).brightness_options())
+            .validate()
+            .unwrap();
+
+        assert_eq!(brightness.level, Some(80));
+        assert_eq!(brightness.levels.step(), 20);
+        assert_eq!(brightness.levels.step(), 100);
+    }
+
+    #[test]
+    fn rejects_brightness_above_100() {
+        let error = args(&["--brightness", "150"])
+            .brightness_options()
+            .validate()
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "invalid level: 150 is out of range, expected 0 to 100"
+        );
+
+        let file: ConfigFile = toml::from_str("[brightness]\nlevels = [100, 120]").unwrap();
+        assert!(matches!(
+            file.brightness.validate(),
+            Err(ConfigError::Invalid("levels", _))
+        ));
+        let file: ConfigFile = toml::from_str("[brightness]\nschedule = \"22:00=150\"").unwrap();
+        assert!(matches!(
+            file.brightness.validate(),
+            Err(ConfigError::Invalid("schedule", _))
+        ));
+    }
+
+    #[test]
+    fn rejects_unsupported_rotations() {
+        let error = args(&["--rotation", "45"])
+            .panel_options()
+            .validate()
+            .err()
+            .unwrap();
+
+        assert!(matches!(error, ConfigError::Invalid("rotation", _)));
+    }
+
+    #[test]
+    fn reads_the_color_calibration() {
+        let file: ConfigFile = toml::from_str(
+            r#"
+            [color]
+            gamma = 2.2
+            blue_gamma = 1.0
+            green_gain = 0.5
+            "#,
+        )
+        .unwrap();
+
+        let calibration = file
+            .color
+            .merge(args(&[]).color_options())
+            .validate()
+            .unwrap();
+
+        assert_eq!(
+            calibration.apply(Rgb888::new(128, 128, 128)),
+            Rgb888::new(56, 28, 128)
+        );
+    }
+
+    #[test]
+    fn rejects_gains_above_one() {
+        let file: ConfigFile = toml::from_str("[color]\nred_gain = 1.5").unwrap();
+
+        assert!(matches!(
+            file.color.validate(),
+            Err(ConfigError::Invalid("red_gain", _))
+        ));
+    }
+
+    #[test]
+    fn picks_fonts_by_name() {
+        let file: ConfigFile = toml::from_str("[fonts]\nclock = \"6x13-bold\"").unwrap();
+        let fonts = file.fonts.validate().unwrap();
+        assert_eq!(fonts.clock.line_height(), 13);
+        assert_eq!(fonts.title, Font::default());
+
+        let file: ConfigFile = toml::from_str("[fonts]\ntitle = \"comic-sans\"").unwrap();
+        assert!(matches!(
+            file.fonts.validate(),
+            Err(ConfigError::Invalid("title", _))
+        ));
+    }
+
+    #[test]
+    fn night_shift_needs_a_complete_schedule() {
+        let file: ConfigFile = toml::from_str("[night]\nstart = \"21:00\"").unwrap();
+        assert!(matches!(
+            file.night.validate(),
+            Err(ConfigError::Invalid("night", _))
+        ));
+
+        let file: ConfigFile = toml::from_str("[night]\nstart = \"21:00\"\nend = \"7am\"").unwrap();
+        assert!(matches!(
+            file.night.validate(),
+            Err(ConfigError::Invalid("night end", _))
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_transitions() {
+        let error = args(&["--transition", "spin"])
+            .transition_options()
+            .validate()
+            .err()
+            .unwrap();
+
+        assert!(matches!(error, ConfigError::Invalid("transition", _)));
+    }
+
+    #[test]
+    fn rejects_unknown_settings() {
+        assert!(toml::from_str::<ConfigFile>("[panel]\nbrightnes = 10").is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub struct FrameScheduler {
+    frame_duration: Duration,
+    next_frame: Instant,
+}
+
+impl FrameScheduler {
+    pub fn new(fps: u32) -> Self {
+        FrameScheduler {
+            frame_duration: Duration::from_secs(1) / fps.max(1),
+            next_frame: Instant::now(),
+        }
+    }
+
+    // Sleeps until the next frame is due, so the main loop never runs faster than the target fps.
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        thread::sleep(self.time_until_next_frame(now));
+        self.advance(now);
+    }
+
+    fn time_until_next_frame(&self, now: Instant) -> Duration {
+        self.next_frame.saturating_duration_since(now)
+    }
+
+    fn advance(&mut self, now: Instant) {
+        self.next_frame += self.frame_duration;
+
+        // Don't try to catch up on frames that were missed while drawing took too long.
+        if self.next_frame < now {
+            self.next_frame = now + self.frame_duration;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::FrameScheduler;
+
+    #[test]
+    fn paces_frames_at_target_fps() {
+        let mut scheduler = FrameScheduler::new(20);
+        let start = scheduler.next_frame;
+
+        assert_eq!(scheduler.time_until_next_frame(start), Duration::ZERO);
+        scheduler.advance(start);
+        assert_eq!(
+            scheduler.time_until_next_frame(start),
+            Duration::from_millis(50)
+        );
+    }
+
+    #[test]
+    fn skips_missed_frames() {
+        let mut scheduler = FrameScheduler::new(20);
+        let late = scheduler.next_frame + Duration::from_millis(500);
+
+        scheduler.advance(late);
+        assert_eq!(
+            scheduler.time_until_next_frame(late),
+            Duration::from_millis(50)
+        );
+    }
+}
@@ -1,12 +1,28 @@
 mod apps;
+mod clock;
+mod config;
+mod frame_scheduler;
 mod modules;
 mod pixel_display;
+#[cfg(test)]
+mod test_util;
 
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc};
 
+#[cfg(feature = "simulated")]
 use crate::apps::launcher::Input;
 
-use crate::{apps::launcher::Launcher, pixel_display::pixel_display::PixelDisplay};
+use crate::{
+    apps::{background::Background, launcher::Launcher},
+    clock::{Clock, SystemClock},
+    config::{Args, Config},
+    frame_scheduler::FrameScheduler,
+    pixel_display::{
+        pixel_display::PixelDisplay,
+        power_limit::{PowerReport, StatsFile},
+        terminal::TerminalEvent,
+    },
+};
 
 #[cfg(feature = "simulated")]
 use embedded_graphics_simulator::sdl2::Keycode;
@@ -19,35 +35,97 @@ use crate::pixel_display::input_handler::InputHandler;
 fn main() -> Result<(), core::convert::Infallible> {
     use crate::pixel_display::pixel_display::{DisplayMode, DisplayOutput};
 
-    #[cfg(feature = "simulated")]
-    let mut pixel_display = PixelDisplay::new(32, 64, DisplayMode::Simulated);
+    let args: Args = argh::from_env();
+    let config = match Config::load(&args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("lumi-dash: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let (tx, rx) = mpsc::channel();
 
     #[cfg(feature = "real")]
-    let mut pixel_display = PixelDisplay::new(32, 64, DisplayMode::Real);
+    if !args.terminal {
+        InputHandler::start(tx.clone());
+    }
 
-    let (tx, rx) = mpsc::channel();
+    // The launcher is created first, Spotify may need the terminal to log in.
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    let backgrounds = match config.backgrounds.directory {
+        Some(ref dir) => Background::load(dir, config.panel.logical_size(), &config.fonts.registry),
+        None => Background::builtin(),
+    };
+    let mut launcher = Launcher::new(
+        tx.clone(),
+        clock.clone(),
+        config.screen_saver,
+        config.transition,
+        backgrounds,
+        config.backgrounds.interval,
+        config.fonts,
+    )
+    .with_brightness_levels(config.brightness.levels);
 
     #[cfg(feature = "real")]
-    InputHandler::start(tx.clone());
+    let mode = DisplayMode::Real;
 
-    let mut launcher = Launcher::new(tx.clone());
+    #[cfg(all(feature = "simulated", not(feature = "real")))]
+    let mode = DisplayMode::Simulated;
+
+    // Built without the panel or the simulator, the terminal is the only output left.
+    #[cfg(not(any(feature = "real", feature = "simulated")))]
+    let mode = DisplayMode::Terminal;
+
+    let mode = if args.terminal {
+        DisplayMode::Terminal
+    } else {
+        mode
+    };
+    let mut pixel_display = PixelDisplay::new(&config.panel, mode);
+
+    pixel_display.set_calibration(config.calibration);
+    pixel_display.set_night_shift(config.night_shift);
+    pixel_display.set_power_limiter(config.power.limiter);
+    pixel_display.set_brightness_schedule(config.brightness.schedule);
+    if let Some(level) = config.brightness.level {
+        pixel_display.set_brightness(level);
+    }
+
+    let mut scheduler = FrameScheduler::new(args.fps);
+    let mut power_report = PowerReport::default();
+    let mut stats_file = config.power.stats_file.map(StatsFile::new);
     'running: loop {
-        pixel_display.update();
-        launcher.draw(&mut pixel_display);
+        scheduler.wait();
 
-        match pixel_display.output {
-            DisplayOutput::Real(ref mut c, ref mut m) => {
-                *c = *m.update_on_vsync(Box::new(c.clone()));
+        let colors_changed = pixel_display.update_colors(clock.as_ref());
+        if launcher.needs_redraw() || colors_changed {
+            pixel_display.update();
+            launcher.draw(&mut pixel_display);
+            pixel_display.present();
 
-                #[cfg(feature = "real")]
-                match rx.try_recv() {
-                    Ok(m) => launcher.handle_input(m),
-                    Err(_) => (),
-                }
+            // The terminal output would be garbled by log lines.
+            let stats = pixel_display.power_stats();
+            let report = power_report.update(stats, clock.instant());
+            if let (Some(line), false) = (report, args.terminal) {
+                eprintln!("lumi-dash: {}", line);
+            }
+            let written = stats_file
+                .as_mut()
+                .map(|f| f.update(stats, clock.instant()));
+            if let (Some(Err(e)), false) = (written, args.terminal) {
+                eprintln!("lumi-dash: couldn't write the power stats: {}", e);
             }
-            DisplayOutput::Simulator(ref s, ref mut w) => {
-                w.update(&s);
-                #[cfg(feature = "simulated")]
+        }
+
+        while let Ok(m) = rx.try_recv() {
+            launcher.handle_input(m);
+        }
+
+        match pixel_display.output {
+            #[cfg(feature = "simulated")]
+            DisplayOutput::Simulator(_, ref mut w) => {
                 for event in w.events() {
                     match event {
                         SimulatorEvent::Quit => break 'running Ok(()),
@@ -57,6 +135,7 @@ fn main() -> Result<(), core::convert::Infallible> {
                                 Keycode::Right => launcher.handle_input(Input::Prev),
                                 Keycode::Down => launcher.handle_input(Input::Pressed),
                                 Keycode::Up => launcher.handle_input(Input::Held),
+                                Keycode::B => launcher.handle_input(Input::Brightness),
                                 _ => (),
                             };
                         }
@@ -64,6 +143,15 @@ fn main() -> Result<(), core::convert::Infallible> {
                     }
                 }
             }
+            DisplayOutput::Terminal(ref mut t) => {
+                for event in t.events() {
+                    match event {
+                        TerminalEvent::Quit => break 'running Ok(()),
+                        TerminalEvent::Input(input) => launcher.handle_input(input),
+                    }
+                }
+            }
+            _ => (),
         }
     }
 }
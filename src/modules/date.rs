@@ -1,14 +1,55 @@
-use crate::pixel_display::pixel_display::PixelDisplay;
-use chrono::prelude::*;
+use std::sync::Arc;
+
+use crate::{
+    clock::Clock,
+    pixel_display::{surface::Surface, text_style::TextStyle},
+};
 use embedded_graphics::geometry::Point;
 
 use super::module::Module;
 
-pub struct Date {}
+pub struct Date {
+    clock: Arc<dyn Clock>,
+    style: TextStyle,
+}
+
+impl Date {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Date {
+            clock,
+            style: TextStyle::default(),
+        }
+    }
+
+    pub fn with_style(self, style: TextStyle) -> Self {
+        Date { style, ..self }
+    }
+}
 
 impl Module for Date {
-    fn draw(&self, point: Point, display: &mut PixelDisplay) {
-        let local: DateTime<Local> = Local::now();
-        display.draw_text(format!("{}", local.format("%d.%m")).as_str(), point);
+    fn draw<S: Surface>(&self, point: Point, display: &mut S) {
+        let local = self.clock.now();
+        let text = format!("{}", local.format("%d.%m"));
+        display.draw_styled_text(&text, point, &self.style);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use embedded_graphics::geometry::Point;
+
+    use super::Date;
+    use crate::clock::FakeClock;
+    use crate::modules::module::Module;
+    use crate::pixel_display::snapshot;
+
+    #[test]
+    fn draws_day_and_month() {
+        let mut display = snapshot::display();
+        let date = Date::new(Arc::new(FakeClock::new(snapshot::fixed_instant())));
+        date.draw(Point::new(23, 6), &mut display);
+        snapshot::assert_snapshot("date", &display);
     }
 }
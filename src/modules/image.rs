@@ -1,25 +1,492 @@
-use crate::pixel_display::pixel_display::PixelDisplay;
+use std::fmt;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use embedded_graphics::{geometry::Point, pixelcolor::Rgb888};
-use tinybmp::Bmp;
+use crate::pixel_display::{
+    framebuffer::Framebuffer,
+    surface::Surface,
+    text_layout::{Overflow, TextBox},
+    text_style::TextStyle,
+};
 
+use embedded_graphics::{
+    geometry::{OriginDimensions, Point, Size},
+    pixelcolor::{Rgb888, RgbColor},
+    prelude::{DrawTarget, Pixel, PointsIter},
+    primitives::Rectangle,
+};
+use image::{
+    codecs::gif::GifDecoder, imageops::FilterType, AnimationDecoder, DynamicImage, ImageFormat,
+};
+
+use super::ken_burns::{KenBurns, KenBurnsSettings};
 use super::module::Module;
 
-pub struct Image<'a> {
-    bitmap: Bmp<'a, Rgb888>,
+const EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "gif", "bmp"];
+const ERROR_BACKGROUND: Rgb888 = Rgb888::new(80, 0, 0);
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+// Panning and zooming is redrawn at 25 frames per second.
+const MOTION_STEP: Duration = Duration::from_millis(40);
+// Like browsers, GIF frames with shorter delays show for the default, most GIFs are made for that.
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+
+#[derive(Debug)]
+pub enum ImageError {
+    Read(PathBuf, std::io::Error),
+    Decode(PathBuf, image::ImageError),
+    Empty(PathBuf),
+    Frames(PathBuf, Size),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Read(path, e) => write!(f, "couldn't read {}: {}", path.display(), e),
+            ImageError::Decode(path, e) => write!(f, "couldn't decode {}: {}", path.display(), e),
+            ImageError::Empty(path) => write!(f, "no images in {}", path.display()),
+            ImageError::Frames(path, size) => write!(
+                f,
+                "{} is smaller than a frame of {}x{}",
+                path.display(),
+                size.width,
+                size.height
+            ),
+        }
+    }
+}
+
+impl ImageError {
+    fn path(&self) -> &Path {
+        match self {
+            ImageError::Read(path, _)
+            | ImageError::Decode(path, _)
+            | ImageError::Empty(path)
+            | ImageError::Frames(path, _) => path,
+        }
+    }
+}
+
+// How a still image is cut into the frames of a sprite sheet, and how long each frame shows.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Animation {
+    // Frames are read row by row, `frames` leaves out empty cells at the end of the sheet.
+    pub frame_size: Option<Size>,
+    pub frames: Option<usize>,
+    // A single delay applies to every frame, GIFs keep their own delays without any.
+    pub frame_delays: Vec<Duration>,
+    // Still images pan and zoom over the panel instead of showing a fixed crop.
+    pub ken_burns: Option<KenBurnsSettings>,
 }
 
-impl Image<'_> {
-    pub fn new(bytes: &[u8]) -> Image<'_> {
+// A decoded image, it is parsed once and drawn from its pixel buffers. Animated images have a
+// buffer and a delay for every frame.
+pub struct Image {
+    frames: Vec<(Framebuffer, Duration)>,
+    ken_burns: Option<KenBurns>,
+}
+
+impl Image {
+    // Bundled images are drawn at their own size.
+    pub fn new(bytes: &[u8]) -> Image {
+        let path = Path::new("bundled image");
+        decode(path, bytes, None, &Animation::default())
+            .unwrap_or_else(|e| Image::error(Size::new(64, 32), &e))
+    }
+
+    // Loads a PNG, JPEG, GIF or BMP file and scales and crops each frame to fill `size`.
+    pub fn load(path: &Path, size: Size, animation: &Animation) -> Result<Image, ImageError> {
+        let bytes = fs::read(path).map_err(|e| ImageError::Read(path.to_path_buf(), e))?;
+
+        decode(path, &bytes, Some(size), animation)
+    }
+
+    // A placeholder for a file that couldn't be loaded, so a bad file doesn't go unnoticed.
+    pub fn error(size: Size, error: &ImageError) -> Image {
+        let mut frame = Framebuffer::new(size);
+        frame.clear(ERROR_BACKGROUND).ok();
+        let (right, bottom) = (size.width as i32 - 1, size.height as i32 - 1);
+        let border = Rectangle::new(Point::zero(), size)
+            .points()
+            .filter(|p| p.x == 0 || p.y == 0 || p.x == right || p.y == bottom)
+            .map(|point| Pixel(point, Rgb888::RED));
+        frame.draw_iter(border).ok();
+
+        let name = error
+            .path()
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let name_box = TextBox::new(Rectangle::new(
+            Point::new(2, 12),
+            Size::new(size.width.saturating_sub(4), 6),
+        ))
+        .with_overflow(Overflow::Ellipsis);
+        frame.draw_text("bad image", Point::new(2, 8));
+        frame.draw_text_box(&name, &name_box, &TextStyle::default());
+
         Image {
-            bitmap: Bmp::<Rgb888>::from_slice(bytes).unwrap(),
+            frames: vec![(frame, Duration::ZERO)],
+            ken_burns: None,
+        }
+    }
+
+    pub fn size(&self) -> Size {
+        match self.ken_burns {
+            Some(ref ken_burns) => ken_burns.size(),
+            None => self.frames[0].0.size(),
         }
     }
+
+    pub fn is_animated(&self) -> bool {
+        self.frames.len() > 1 || self.ken_burns.is_some()
+    }
+
+    // The frame showing `elapsed` after the animation started, it loops forever.
+    pub fn frame_at(&self, elapsed: Duration) -> usize {
+        if self.ken_burns.is_some() {
+            return (elapsed.as_millis() / MOTION_STEP.as_millis()) as usize;
+        }
+        let total: Duration = self.frames.iter().map(|(_, delay)| *delay).sum();
+        if !self.is_animated() || total.is_zero() {
+            return 0;
+        }
+
+        let mut remaining = Duration::from_nanos((elapsed.as_nanos() % total.as_nanos()) as u64);
+        for (index, (_, delay)) in self.frames.iter().enumerate() {
+            if remaining < *delay {
+                return index;
+            }
+            remaining -= *delay;
+        }
+        0
+    }
+
+    pub fn draw_frame<S: Surface>(&self, index: usize, point: Point, display: &mut S) {
+        match self.ken_burns {
+            Some(ref ken_burns) => ken_burns.draw(MOTION_STEP * index as u32, point, display),
+            None => display.blit(&self.frames[index].0, point, u8::MAX),
+        }
+    }
+}
+
+impl Module for Image {
+    fn draw<S: Surface>(&self, point: Point, display: &mut S) {
+        self.draw_frame(0, point, display);
+    }
+}
+
+// Every supported image in `dir`, sorted by file name.
+pub fn list_dir(dir: &Path) -> Result<Vec<PathBuf>, ImageError> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| ImageError::Read(dir.to_path_buf(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| is_image(path))
+        .collect();
+    paths.sort();
+
+    if paths.is_empty() {
+        return Err(ImageError::Empty(dir.to_path_buf()));
+    }
+    Ok(paths)
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()))
+}
+
+fn decode(
+    path: &Path,
+    bytes: &[u8],
+    size: Option<Size>,
+    animation: &Animation,
+) -> Result<Image, ImageError> {
+    let error = |e| ImageError::Decode(path.to_path_buf(), e);
+
+    let frames: Vec<(DynamicImage, Duration)> = match image::guess_format(bytes) {
+        Ok(ImageFormat::Gif) => GifDecoder::new(Cursor::new(bytes))
+            .and_then(|decoder| decoder.into_frames().collect_frames())
+            .map_err(error)?
+            .into_iter()
+            .map(|frame| {
+                let delay = match Duration::from(frame.delay()) {
+                    delay if delay < MIN_FRAME_DELAY => DEFAULT_FRAME_DELAY,
+                    delay => delay,
+                };
+                (DynamicImage::ImageRgba8(frame.into_buffer()), delay)
+            })
+            .collect(),
+        _ => split(
+            path,
+            image::load_from_memory(bytes).map_err(error)?,
+            animation,
+        )?
+        .into_iter()
+        .map(|frame| (frame, DEFAULT_FRAME_DELAY))
+        .collect(),
+    };
+
+    // A GIF can end before its first image.
+    if frames.is_empty() {
+        return Err(ImageError::Empty(path.to_path_buf()));
+    }
+
+    // Panning and zooming samples the image itself, fitted frames would never be drawn.
+    if let ([(image, _)], Some(size), Some(settings)) = (&frames[..], size, animation.ken_burns) {
+        return Ok(Image {
+            frames: Vec::new(),
+            ken_burns: Some(KenBurns::new(image, size, settings)),
+        });
+    }
+
+    Ok(Image {
+        ken_burns: None,
+        frames: frames
+            .into_iter()
+            .enumerate()
+            .map(|(index, (frame, delay))| {
+                let frame = match size {
+                    Some(size) => fit(&frame, size),
+                    None => to_frame(&frame),
+                };
+                match animation.frame_delays.len() {
+                    0 => (frame, delay),
+                    count => (frame, animation.frame_delays[index % count]),
+                }
+            })
+            .collect(),
+    })
+}
+
+fn split(
+    path: &Path,
+    image: DynamicImage,
+    animation: &Animation,
+) -> Result<Vec<DynamicImage>, ImageError> {
+    let frame = match animation.frame_size {
+        Some(frame) => frame,
+        None => return Ok(vec![image]),
+    };
+
+    let columns = image.width() / frame.width.max(1);
+    let rows = image.height() / frame.height.max(1);
+    let count = (columns * rows) as usize;
+    let count = animation.frames.map_or(count, |frames| frames.min(count));
+    if count == 0 {
+        return Err(ImageError::Frames(path.to_path_buf(), frame));
+    }
+
+    Ok((0..count as u32)
+        .map(|index| {
+            let x = index % columns * frame.width;
+            let y = index / columns * frame.height;
+            image.crop_imm(x, y, frame.width, frame.height)
+        })
+        .collect())
+}
+
+// Scales the image to cover `size` and crops what sticks out around the center.
+pub fn fit(image: &DynamicImage, size: Size) -> Framebuffer {
+    if image.width() == size.width && image.height() == size.height {
+        return to_frame(image);
+    }
+
+    to_frame(&image.resize_to_fill(size.width, size.height, FilterType::Lanczos3))
+}
+
+fn to_frame(image: &DynamicImage) -> Framebuffer {
+    let image = image.to_rgb8();
+    let mut frame = Framebuffer::new(Size::new(image.width(), image.height()));
+    let pixels = image.enumerate_pixels().map(|(x, y, p)| {
+        Pixel(
+            Point::new(x as i32, y as i32),
+            Rgb888::new(p[0], p[1], p[2]),
+        )
+    });
+    frame.draw_iter(pixels).ok();
+
+    frame
 }
 
-impl Module for Image<'_> {
-    fn draw(&self, point: Point, display: &mut PixelDisplay) {
-        let image = embedded_graphics::image::Image::new(&self.bitmap, Point::new(0, 0));
-        display.draw_image(image, point);
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    use embedded_graphics::geometry::{Point, Size};
+    use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+    use image::{ImageFormat, Rgb, RgbImage};
+
+    use super::{list_dir, Animation, Image, ImageError};
+    use crate::modules::ken_burns::KenBurnsSettings;
+    use crate::modules::module::Module;
+    use crate::pixel_display::framebuffer::Framebuffer;
+    use crate::pixel_display::snapshot;
+    use crate::test_util;
+
+    #[test]
+    fn draws_bitmap() {
+        let gradient = RgbImage::from_fn(24, 16, |x, y| Rgb([x as u8 * 10, y as u8 * 15, 120]));
+        let mut bitmap = Cursor::new(Vec::new());
+        gradient.write_to(&mut bitmap, ImageFormat::Bmp).unwrap();
+
+        let mut display = snapshot::display();
+        Image::new(bitmap.get_ref()).draw(Point::new(20, 8), &mut display);
+        snapshot::assert_snapshot("image", &display);
+    }
+
+    #[test]
+    fn loads_images_from_a_directory_scaled_to_the_panel() {
+        let dir = test_util::temp_dir();
+        RgbImage::from_pixel(128, 128, Rgb([0, 0, 255]))
+            .save(dir.join("blue.png"))
+            .unwrap();
+        fs::write(dir.join("broken.jpg"), b"not a jpeg").unwrap();
+        fs::write(dir.join("notes.txt"), b"ignored").unwrap();
+
+        let paths = list_dir(&dir).unwrap();
+        let blue = Image::load(&paths[0], Size::new(64, 32), &Animation::default()).unwrap();
+        let broken = Image::load(&paths[1], Size::new(64, 32), &Animation::default());
+
+        assert_eq!(paths.len(), 2);
+        assert_eq!(blue.size(), Size::new(64, 32));
+        assert_eq!(
+            blue.frames[0].0.pixel(Point::new(63, 31)),
+            Some(Rgb888::BLUE)
+        );
+        let error = Image::error(Size::new(64, 32), &broken.err().unwrap());
+        assert_eq!(error.frames[0].0.pixel(Point::zero()), Some(Rgb888::RED));
+    }
+
+    #[test]
+    fn reports_missing_files() {
+        let error = Image::load(
+            "missing.png".as_ref(),
+            Size::new(64, 32),
+            &Animation::default(),
+        )
+        .err()
+        .unwrap();
+
+        assert!(matches!(error, ImageError::Read(_, _)));
+    }
+
+    #[test]
+    fn reports_gifs_without_frames() {
+        let dir = test_util::temp_dir();
+        let path = dir.join("blank.gif");
+        // A 1x1 screen descriptor and the trailer, the decoder reads one byte past the trailer.
+        fs::write(&path, b"GIF89a\x01\x00\x01\x00\x00\x00\x00;\n").unwrap();
+
+        let error = Image::load(&path, Size::new(64, 32), &Animation::default())
+            .err()
+            .unwrap();
+
+        assert!(matches!(error, ImageError::Empty(_)));
+    }
+
+    #[test]
+    fn plays_gif_frames_with_their_delays() {
+        let dir = test_util::temp_dir();
+        let path = dir.join("stars.gif");
+        test_util::write_gif(
+            &path,
+            Size::new(64, 32),
+            &[(Rgb888::RED, 50), (Rgb888::BLUE, 150)],
+        );
+
+        let image = Image::load(&path, Size::new(64, 32), &Animation::default()).unwrap();
+
+        assert!(image.is_animated());
+        assert_eq!(image.frames[0].0.pixel(Point::zero()), Some(Rgb888::RED));
+        assert_eq!(image.frame_at(Duration::from_millis(49)), 0);
+        assert_eq!(image.frame_at(Duration::from_millis(50)), 1);
+        assert_eq!(image.frame_at(Duration::from_millis(210)), 0);
+    }
+
+    #[test]
+    fn slows_down_only_gif_frames_that_are_too_fast() {
+        let dir = test_util::temp_dir();
+        let path = dir.join("flicker.gif");
+        test_util::write_gif(
+            &path,
+            Size::new(16, 8),
+            &[(Rgb888::RED, 10), (Rgb888::BLUE, 10)],
+        );
+
+        let image = Image::load(&path, Size::new(16, 8), &Animation::default()).unwrap();
+        assert_eq!(image.frames[0].1, Duration::from_millis(100));
+
+        let animation = Animation {
+            frame_delays: vec![Duration::from_millis(10)],
+            ..Default::default()
+        };
+        let image = Image::load(&path, Size::new(16, 8), &animation).unwrap();
+        assert_eq!(image.frames[0].1, Duration::from_millis(10));
+    }
+
+    #[test]
+    fn pans_stills_without_fitting_them() {
+        let dir = test_util::temp_dir();
+        let path = dir.join("valley.png");
+        RgbImage::from_pixel(128, 64, Rgb([0, 120, 0]))
+            .save(&path)
+            .unwrap();
+        let animation = Animation {
+            ken_burns: Some(KenBurnsSettings::default()),
+            ..Default::default()
+        };
+
+        let image = Image::load(&path, Size::new(64, 32), &animation).unwrap();
+        let mut frame = Framebuffer::new(Size::new(64, 32));
+        image.draw_frame(
+            image.frame_at(Duration::from_secs(5)),
+            Point::zero(),
+            &mut frame,
+        );
+
+        assert!(image.frames.is_empty());
+        assert_eq!(image.size(), Size::new(64, 32));
+        assert_eq!(
+            frame.pixel(Point::new(63, 31)),
+            Some(Rgb888::new(0, 120, 0))
+        );
+    }
+
+    #[test]
+    fn cuts_sprite_sheets_into_frames() {
+        let dir = test_util::temp_dir();
+        let path = dir.join("petals.png");
+        let mut sheet = RgbImage::new(32, 16);
+        for (x, y, pixel) in sheet.enumerate_pixels_mut() {
+            *pixel = Rgb([(x / 16 * 100) as u8, (y / 8 * 100) as u8, 0]);
+        }
+        sheet.save(&path).unwrap();
+
+        let animation = Animation {
+            frame_size: Some(Size::new(16, 8)),
+            frames: Some(3),
+            frame_delays: vec![Duration::from_millis(80)],
+            ..Default::default()
+        };
+        let image = Image::load(&path, Size::new(16, 8), &animation).unwrap();
+
+        let colors: Vec<_> = image
+            .frames
+            .iter()
+            .map(|(frame, delay)| (frame.pixel(Point::zero()).unwrap(), delay.as_millis()))
+            .collect();
+        assert_eq!(
+            colors,
+            [
+                (Rgb888::new(0, 0, 0), 80),
+                (Rgb888::new(100, 0, 0), 80),
+                (Rgb888::new(0, 100, 0), 80)
+            ]
+        );
     }
 }
@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::pixel_display::framebuffer::Framebuffer;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ImageKey {
+    Album(String),
+    Url(String),
+}
+
+// Decoded images by key, the least recently used one is dropped once `capacity` is reached.
+// Images that failed to decode are kept as `None`, so they aren't tried again.
+pub struct ImageCache {
+    capacity: usize,
+    entries: VecDeque<(ImageKey, Option<Arc<Framebuffer>>)>,
+}
+
+impl ImageCache {
+    pub fn new(capacity: usize) -> Self {
+        ImageCache {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    // `Some(None)` for an image that failed to decode.
+    pub fn get(&mut self, key: &ImageKey) -> Option<Option<Arc<Framebuffer>>> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(index)?;
+        let image = entry.1.clone();
+        self.entries.push_front(entry);
+
+        Some(image)
+    }
+
+    // `decode` runs at most once per key, also when it fails.
+    pub fn get_or_insert_with<F>(&mut self, key: ImageKey, decode: F) -> Option<Arc<Framebuffer>>
+    where
+        F: FnOnce() -> Option<Framebuffer>,
+    {
+        if let Some(image) = self.get(&key) {
+            return image;
+        }
+
+        let image = decode().map(Arc::new);
+        self.entries.truncate(self.capacity.saturating_sub(1));
+        self.entries.push_front((key, image.clone()));
+
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use embedded_graphics::geometry::Size;
+
+    use super::{ImageCache, ImageKey};
+    use crate::pixel_display::framebuffer::Framebuffer;
+
+    fn album(id: &str) -> ImageKey {
+        ImageKey::Album(id.to_string())
+    }
+
+    #[test]
+    fn decodes_each_image_once() {
+        let mut cache = ImageCache::new(4);
+        let mut decoded = 0;
+        let mut decode = || {
+            decoded += 1;
+            Some(Framebuffer::new(Size::new(32, 32)))
+        };
+
+        let first = cache.get_or_insert_with(album("a"), &mut decode).unwrap();
+        let second = cache.get_or_insert_with(album("a"), &mut decode).unwrap();
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(decoded, 1);
+    }
+
+    #[test]
+    fn remembers_images_that_failed_to_decode() {
+        let mut cache = ImageCache::new(4);
+        let key = ImageKey::Url("https://i.scdn.co/image/missing".to_string());
+        let mut attempts = 0;
+        let mut decode = || {
+            attempts += 1;
+            None
+        };
+
+        assert!(cache.get_or_insert_with(key.clone(), &mut decode).is_none());
+        assert!(cache.get_or_insert_with(key.clone(), &mut decode).is_none());
+
+        assert!(matches!(cache.get(&key), Some(None)));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn drops_the_least_recently_used_image() {
+        let mut cache = ImageCache::new(2);
+        let decode = || Some(Framebuffer::new(Size::new(1, 1)));
+
+        cache.get_or_insert_with(album("a"), decode);
+        cache.get_or_insert_with(album("b"), decode);
+        cache.get(&album("a"));
+        cache.get_or_insert_with(album("c"), decode);
+
+        assert!(cache.get(&album("a")).is_some());
+        assert!(cache.get(&album("b")).is_none());
+    }
+}
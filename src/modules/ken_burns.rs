@@ -0,0 +1,245 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::primitives::Rectangle;
+use image::{imageops::FilterType, DynamicImage, RgbImage};
+
+use crate::pixel_display::surface::Surface;
+
+// Where the visible window starts and ends on the image, it moves back and forth between them.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PanPath {
+    LeftToRight,
+    RightToLeft,
+    TopToBottom,
+    BottomToTop,
+    Diagonal,
+    Center,
+}
+
+impl FromStr for PanPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left-right" => Ok(PanPath::LeftToRight),
+            "right-left" => Ok(PanPath::RightToLeft),
+            "top-bottom" => Ok(PanPath::TopToBottom),
+            "bottom-top" => Ok(PanPath::BottomToTop),
+            "diagonal" => Ok(PanPath::Diagonal),
+            "center" => Ok(PanPath::Center),
+            _ => Err(format!(
+                "unknown pan '{}', expected left-right, right-left, top-bottom, bottom-top, \
+                 diagonal or center",
+                s
+            )),
+        }
+    }
+}
+
+impl PanPath {
+    // Positions of the window as fractions of the room it has to move in.
+    fn anchors(&self) -> ((f32, f32), (f32, f32)) {
+        match self {
+            PanPath::LeftToRight => ((0.0, 0.5), (1.0, 0.5)),
+            PanPath::RightToLeft => ((1.0, 0.5), (0.0, 0.5)),
+            PanPath::TopToBottom => ((0.5, 0.0), (0.5, 1.0)),
+            PanPath::BottomToTop => ((0.5, 1.0), (0.5, 0.0)),
+            PanPath::Diagonal => ((0.0, 0.0), (1.0, 1.0)),
+            PanPath::Center => ((0.5, 0.5), (0.5, 0.5)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct KenBurnsSettings {
+    pub path: PanPath,
+    // The time one pass along the path takes.
+    pub period: Duration,
+    // Zoom at the start and end of the path, 1.0 shows as much of the image as fits the panel.
+    pub zoom: (f32, f32),
+}
+
+impl Default for KenBurnsSettings {
+    fn default() -> Self {
+        KenBurnsSettings {
+            path: PanPath::Diagonal,
+            period: Duration::from_secs(30),
+            zoom: (1.0, 1.2),
+        }
+    }
+}
+
+// Slowly pans and zooms a window over an image, sampling it between pixels so it moves smoothly.
+pub struct KenBurns {
+    source: RgbImage,
+    size: Size,
+    settings: KenBurnsSettings,
+}
+
+impl KenBurns {
+    pub fn new(image: &DynamicImage, size: Size, settings: KenBurnsSettings) -> Self {
+        // Sampling far fewer pixels than the image has skips detail, so large photos are scaled
+        // down to what the closest zoom needs first.
+        let zoom = settings.zoom.0.max(settings.zoom.1);
+        let cover = (size.width as f32 / image.width() as f32)
+            .max(size.height as f32 / image.height() as f32);
+        let scale = (cover * zoom).min(1.0);
+        let source = match scale {
+            scale if scale < 1.0 => image
+                .resize_exact(
+                    (image.width() as f32 * scale).round().max(1.0) as u32,
+                    (image.height() as f32 * scale).round().max(1.0) as u32,
+                    FilterType::Lanczos3,
+                )
+                .to_rgb8(),
+            _ => image.to_rgb8(),
+        };
+
+        KenBurns {
+            source,
+            size,
+            settings,
+        }
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    pub fn draw<S: Surface>(&self, elapsed: Duration, point: Point, display: &mut S) {
+        let (x, y, width, height) = self.viewport(elapsed);
+        let step_x = width / self.size.width as f32;
+        let step_y = height / self.size.height as f32;
+
+        let colors = (0..self.size.height).flat_map(|row| {
+            (0..self.size.width).map(move |column| {
+                self.sample(
+                    x + (column as f32 + 0.5) * step_x - 0.5,
+                    y + (row as f32 + 0.5) * step_y - 0.5,
+                )
+            })
+        });
+        display
+            .fill_contiguous(&Rectangle::new(point, self.size), colors)
+            .ok();
+    }
+
+    // The part of the image that fills the panel `elapsed` into the animation, as x, y, width and
+    // height in image pixels.
+    fn viewport(&self, elapsed: Duration) -> (f32, f32, f32, f32) {
+        let (source_width, source_height) =
+            (self.source.width() as f32, self.source.height() as f32);
+        let (width, height) = (self.size.width as f32, self.size.height as f32);
+
+        let progress = self.progress(elapsed);
+        let (start_zoom, end_zoom) = self.settings.zoom;
+        let zoom = lerp(start_zoom, end_zoom, progress);
+        let fit = (source_width / width).min(source_height / height);
+        let (width, height) = (width * fit / zoom, height * fit / zoom);
+
+        let (from, to) = self.settings.path.anchors();
+        let x = (source_width - width) * lerp(from.0, to.0, progress);
+        let y = (source_height - height) * lerp(from.1, to.1, progress);
+
+        (x, y, width, height)
+    }
+
+    // Goes from 0 to 1 and back, easing in and out at both ends.
+    fn progress(&self, elapsed: Duration) -> f32 {
+        let period = self.settings.period.as_secs_f64();
+        if period == 0.0 {
+            return 0.0;
+        }
+
+        let cycle = (elapsed.as_secs_f64() / period % 2.0) as f32;
+        let p = if cycle < 1.0 { cycle } else { 2.0 - cycle };
+        p * p * (3.0 - 2.0 * p)
+    }
+
+    // Bilinear interpolation between the four pixels around `x`, `y`.
+    fn sample(&self, x: f32, y: f32) -> Rgb888 {
+        let (max_x, max_y) = (self.source.width() - 1, self.source.height() - 1);
+        let x = x.clamp(0.0, max_x as f32);
+        let y = y.clamp(0.0, max_y as f32);
+        let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(max_x), (y0 + 1).min(max_y));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+        let [a, b, c, d] =
+            [(x0, y0), (x1, y0), (x0, y1), (x1, y1)].map(|(x, y)| self.source[(x, y)]);
+        let channel = |i: usize| {
+            let top = lerp(a[i] as f32, b[i] as f32, fx);
+            let bottom = lerp(c[i] as f32, d[i] as f32, fx);
+            lerp(top, bottom, fy).round() as u8
+        };
+
+        Rgb888::new(channel(0), channel(1), channel(2))
+    }
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use embedded_graphics::geometry::Size;
+    use embedded_graphics::pixelcolor::Rgb888;
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    use super::{KenBurns, KenBurnsSettings, PanPath};
+
+    fn pan(image: RgbImage, path: PanPath, zoom: (f32, f32)) -> KenBurns {
+        let settings = KenBurnsSettings {
+            path,
+            period: Duration::from_secs(10),
+            zoom,
+        };
+        KenBurns::new(&DynamicImage::ImageRgb8(image), Size::new(64, 32), settings)
+    }
+
+    #[test]
+    fn pans_along_the_path_and_back() {
+        let ken_burns = pan(RgbImage::new(128, 32), PanPath::LeftToRight, (1.0, 1.0));
+
+        assert_eq!(ken_burns.viewport(Duration::ZERO), (0.0, 0.0, 64.0, 32.0));
+        assert_eq!(
+            ken_burns.viewport(Duration::from_secs(5)),
+            (32.0, 0.0, 64.0, 32.0)
+        );
+        assert_eq!(
+            ken_burns.viewport(Duration::from_secs(10)),
+            (64.0, 0.0, 64.0, 32.0)
+        );
+        assert_eq!(
+            ken_burns.viewport(Duration::from_secs(20)),
+            (0.0, 0.0, 64.0, 32.0)
+        );
+    }
+
+    #[test]
+    fn zooms_into_the_center() {
+        let ken_burns = pan(RgbImage::new(64, 32), PanPath::Center, (1.0, 2.0));
+
+        assert_eq!(
+            ken_burns.viewport(Duration::from_secs(10)),
+            (16.0, 8.0, 32.0, 16.0)
+        );
+    }
+
+    #[test]
+    fn samples_between_pixels() {
+        let mut image = RgbImage::new(64, 32);
+        image.put_pixel(1, 0, Rgb([255, 255, 255]));
+        let ken_burns = pan(image, PanPath::Center, (1.0, 1.0));
+
+        assert_eq!(ken_burns.sample(0.5, 0.0), Rgb888::new(128, 128, 128));
+        assert_eq!(ken_burns.sample(1.0, 0.25), Rgb888::new(191, 191, 191));
+        assert_eq!(ken_burns.sample(-3.0, 0.0), Rgb888::new(0, 0, 0));
+    }
+}
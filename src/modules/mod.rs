@@ -1,4 +1,6 @@
 pub mod date;
 pub mod image;
+pub mod image_cache;
+pub mod ken_burns;
 pub mod module;
 pub mod time;
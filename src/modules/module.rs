@@ -1,7 +1,7 @@
 use embedded_graphics::geometry::Point;
 
-use crate::pixel_display::pixel_display::PixelDisplay;
+use crate::pixel_display::surface::Surface;
 
 pub trait Module {
-    fn draw(&self, point: Point, display: &mut PixelDisplay);
+    fn draw<S: Surface>(&self, point: Point, display: &mut S);
 }
@@ -1,14 +1,107 @@
-use crate::pixel_display::pixel_display::PixelDisplay;
-use chrono::prelude::*;
+use std::sync::Arc;
+
+use crate::{
+    clock::Clock,
+    pixel_display::{surface::Surface, text_style::TextStyle},
+};
 use embedded_graphics::geometry::Point;
+use embedded_graphics::primitives::Rectangle;
 
 use super::module::Module;
 
-pub struct Time {}
+pub struct Time {
+    clock: Arc<dyn Clock>,
+    style: TextStyle,
+}
+
+impl Time {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Time {
+            clock,
+            style: TextStyle::default(),
+        }
+    }
+
+    pub fn with_style(self, style: TextStyle) -> Self {
+        Time { style, ..self }
+    }
+
+    // Where the time drawn at `point` ends up, so it can be placed by its size.
+    pub fn bounding_box(&self, point: Point) -> Rectangle {
+        self.style.font.bounding_box(&self.text(), point)
+    }
+
+    fn text(&self) -> String {
+        format!("{}", self.clock.now().format("%R"))
+    }
+}
 
 impl Module for Time {
-    fn draw(&self, point: Point, display: &mut PixelDisplay) {
-        let local: DateTime<Local> = Local::now();
-        display.draw_text(format!("{}", local.format("%R")).as_str(), point);
+    fn draw<S: Surface>(&self, point: Point, display: &mut S) {
+        display.draw_styled_text(&self.text(), point, &self.style);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use embedded_graphics::draw_target::DrawTarget;
+    use embedded_graphics::geometry::{Point, Size};
+    use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+
+    use super::Time;
+    use crate::clock::FakeClock;
+    use crate::config::PanelConfig;
+    use crate::modules::module::Module;
+    use crate::pixel_display::framebuffer::Framebuffer;
+    use crate::pixel_display::orientation::{Orientation, Rotation};
+    use crate::pixel_display::snapshot;
+    use crate::pixel_display::text_style::{TextColor, TextStyle};
+
+    #[test]
+    fn draws_hours_and_minutes() {
+        let mut display = snapshot::display();
+        let time = Time::new(Arc::new(FakeClock::new(snapshot::fixed_instant())));
+        time.draw(Point::new(2, 6), &mut display);
+        snapshot::assert_snapshot("time", &display);
+    }
+
+    #[test]
+    fn draws_an_outline_around_the_digits() {
+        let mut display = snapshot::display();
+        let time = Time::new(Arc::new(FakeClock::new(snapshot::fixed_instant())))
+            .with_style(TextStyle::new(Rgb888::new(255, 165, 0)).with_outline(Rgb888::BLUE));
+        time.draw(Point::new(2, 6), &mut display);
+        snapshot::assert_snapshot("time_outlined", &display);
+    }
+
+    #[test]
+    fn picks_a_color_that_stands_out_from_the_background() {
+        let mut layer = Framebuffer::new(Size::new(64, 32));
+        layer.clear(Rgb888::new(255, 183, 197)).unwrap();
+        let time =
+            Time::new(Arc::new(FakeClock::new(snapshot::fixed_instant()))).with_style(TextStyle {
+                color: TextColor::Auto,
+                ..Default::default()
+            });
+        time.draw(Point::new(2, 6), &mut layer);
+
+        assert!(layer.pixels().contains(&Rgb888::BLACK));
+        assert!(!layer.pixels().contains(&Rgb888::WHITE));
+    }
+
+    #[test]
+    fn draws_on_an_upside_down_panel() {
+        let mut display = snapshot::display_for(&PanelConfig {
+            orientation: Orientation {
+                rotation: Rotation::Clockwise180,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let time = Time::new(Arc::new(FakeClock::new(snapshot::fixed_instant())));
+        time.draw(Point::new(2, 6), &mut display);
+        snapshot::assert_snapshot("time_rotated", &display);
     }
 }
@@ -0,0 +1,253 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use chrono::NaiveTime;
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+
+const FADE_DURATION: Duration = Duration::from_secs(3);
+// From full brightness down to a level for a dark room.
+pub const DEFAULT_LEVELS: [u8; 4] = [100, 60, 30, 10];
+
+#[derive(Clone, Default)]
+pub struct BrightnessSchedule {
+    levels: Vec<(NaiveTime, u8)>,
+}
+
+impl BrightnessSchedule {
+    // The entry that was last started before `time`, entries from the previous day wrap around.
+    fn entry_at(&self, time: NaiveTime) -> Option<(NaiveTime, u8)> {
+        self.levels
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= time)
+            .or(self.levels.last())
+            .copied()
+    }
+}
+
+// Parses schedules like `07:00=100,22:00=15`.
+impl FromStr for BrightnessSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut levels = s
+            .split(',')
+            .map(|entry| {
+                let (time, level) = entry
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected HH:MM=level, got '{}'", entry))?;
+                let time = NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                    .map_err(|_| format!("invalid time '{}', expected HH:MM", time.trim()))?;
+
+                Ok((time, parse_level(level.trim())?))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        levels.sort_by_key(|(time, _)| *time);
+
+        Ok(BrightnessSchedule { levels })
+    }
+}
+
+pub struct BrightnessSettings {
+    // Overrides the schedule until its next entry.
+    pub level: Option<u8>,
+    pub schedule: BrightnessSchedule,
+    pub levels: BrightnessLevels,
+}
+
+// The levels the brightness input steps through, after the last one it starts over. The display
+// is taken to be at the first level at startup.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BrightnessLevels {
+    levels: Vec<u8>,
+    current: usize,
+}
+
+impl Default for BrightnessLevels {
+    fn default() -> Self {
+        BrightnessLevels::new(DEFAULT_LEVELS.to_vec())
+    }
+}
+
+impl BrightnessLevels {
+    // `levels` can't be empty.
+    pub fn new(levels: Vec<u8>) -> Self {
+        BrightnessLevels { levels, current: 0 }
+    }
+
+    pub fn step(&mut self) -> u8 {
+        self.current = (self.current + 1) % self.levels.len();
+        self.levels[self.current]
+    }
+}
+
+fn parse_level(value: &str) -> Result<u8, String> {
+    match value.parse::<u8>() {
+        Ok(level) if level <= 100 => Ok(level),
+        _ => Err(format!(
+            "brightness must be between 0 and 100, got '{}'",
+            value
+        )),
+    }
+}
+
+pub struct Brightness {
+    schedule: BrightnessSchedule,
+    manual: Option<u8>,
+    active_entry: Option<NaiveTime>,
+    current: f32,
+    last_update: Option<Instant>,
+}
+
+impl Default for Brightness {
+    fn default() -> Self {
+        Brightness {
+            schedule: BrightnessSchedule::default(),
+            manual: None,
+            active_entry: None,
+            current: 100.0,
+            last_update: None,
+        }
+    }
+}
+
+impl Brightness {
+    pub fn set_schedule(&mut self, schedule: BrightnessSchedule) {
+        self.schedule = schedule;
+    }
+
+    // Overrides the schedule until it reaches its next entry.
+    pub fn set_level(&mut self, level: u8) {
+        self.manual = Some(level.min(100));
+    }
+
+    pub fn level(&self) -> u8 {
+        self.current.round() as u8
+    }
+
+    // Moves the current level towards the target, returns whether the output changed.
+    pub fn update(&mut self, now: NaiveTime, instant: Instant) -> bool {
+        let entry = self.schedule.entry_at(now);
+        let entry_start = entry.map(|(start, _)| start);
+        if self.last_update.is_some() && entry_start != self.active_entry {
+            self.manual = None;
+        }
+        self.active_entry = entry_start;
+
+        let target = self.manual.or(entry.map(|(_, level)| level)).unwrap_or(100) as f32;
+        let previous = self.scale();
+
+        self.current = match self.last_update {
+            None => target,
+            Some(last) => {
+                let step = 100.0 * (instant - last).as_secs_f32() / FADE_DURATION.as_secs_f32();
+                if (target - self.current).abs() <= step {
+                    target
+                } else {
+                    self.current + step * (target - self.current).signum()
+                }
+            }
+        };
+        self.last_update = Some(instant);
+
+        self.scale() != previous
+    }
+
+    pub fn apply(&self, color: Rgb888) -> Rgb888 {
+        let scale = self.scale() as u16;
+        let channel = |c: u8| (c as u16 * scale / 255) as u8;
+
+        Rgb888::new(channel(color.r()), channel(color.g()), channel(color.b()))
+    }
+
+    fn scale(&self) -> u8 {
+        (self.level() as u16 * 255 / 100) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use chrono::NaiveTime;
+    use embedded_graphics::pixelcolor::Rgb888;
+
+    use super::{Brightness, BrightnessLevels, BrightnessSchedule};
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn schedule_wraps_around_midnight() {
+        let schedule: BrightnessSchedule = "22:00=15, 07:00=100".parse().unwrap();
+
+        assert_eq!(schedule.entry_at(time(12, 0)), Some((time(7, 0), 100)));
+        assert_eq!(schedule.entry_at(time(23, 30)), Some((time(22, 0), 15)));
+        assert_eq!(schedule.entry_at(time(3, 0)), Some((time(22, 0), 15)));
+    }
+
+    #[test]
+    fn schedule_rejects_invalid_entries() {
+        assert!("22:00".parse::<BrightnessSchedule>().is_err());
+        assert!("25:00=10".parse::<BrightnessSchedule>().is_err());
+        assert!("22:00=150".parse::<BrightnessSchedule>().is_err());
+    }
+
+    #[test]
+    fn fades_to_the_scheduled_level() {
+        let mut brightness = Brightness::default();
+        brightness.set_schedule("07:00=100,22:00=20".parse().unwrap());
+        let start = Instant::now();
+
+        brightness.update(time(21, 59), start);
+        assert_eq!(brightness.level(), 100);
+
+        assert!(brightness.update(time(22, 0), start + Duration::from_millis(1500)));
+        assert_eq!(brightness.level(), 50);
+
+        brightness.update(time(22, 0), start + Duration::from_secs(3));
+        assert_eq!(brightness.level(), 20);
+        assert!(!brightness.update(time(22, 1), start + Duration::from_secs(4)));
+    }
+
+    #[test]
+    fn manual_level_lasts_until_the_next_entry() {
+        let mut brightness = Brightness::default();
+        brightness.set_schedule("07:00=100,22:00=20".parse().unwrap());
+        let start = Instant::now();
+
+        brightness.update(time(12, 0), start);
+        brightness.set_level(60);
+        brightness.update(time(12, 0), start + Duration::from_secs(10));
+        assert_eq!(brightness.level(), 60);
+
+        brightness.update(time(22, 0), start + Duration::from_secs(20));
+        assert_eq!(brightness.level(), 20);
+    }
+
+    #[test]
+    fn steps_through_the_levels() {
+        let mut levels = BrightnessLevels::default();
+
+        let stepped: Vec<u8> = (0..5).map(|_| levels.step()).collect();
+
+        assert_eq!(stepped, [60, 30, 10, 100, 60]);
+    }
+
+    #[test]
+    fn scales_colors() {
+        let mut brightness = Brightness::default();
+        assert_eq!(
+            brightness.apply(Rgb888::new(255, 128, 0)),
+            Rgb888::new(255, 128, 0)
+        );
+
+        brightness.set_level(0);
+        brightness.update(time(12, 0), Instant::now());
+        assert_eq!(
+            brightness.apply(Rgb888::new(255, 128, 0)),
+            Rgb888::new(0, 0, 0)
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::Instant;
+
+use chrono::{DateTime, Local};
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+
+use super::brightness::Brightness;
+use super::night_shift::NightShift;
+
+// Per-channel lookup tables for the gamma curve and white balance of the panel.
+#[derive(Clone)]
+pub struct Calibration {
+    tables: [[u8; 256]; 3],
+}
+
+impl Calibration {
+    // `gamma` and `gains` are given as red, green and blue.
+    pub fn new(gamma: [f32; 3], gains: [f32; 3]) -> Self {
+        let mut tables = [[0; 256]; 3];
+        for (channel, table) in tables.iter_mut().enumerate() {
+            for (value, entry) in table.iter_mut().enumerate() {
+                let linear = (value as f32 / 255.0).powf(gamma[channel]);
+                *entry = (linear * gains[channel] * 255.0).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+
+        Calibration { tables }
+    }
+
+    pub fn apply(&self, color: Rgb888) -> Rgb888 {
+        Rgb888::new(
+            self.tables[0][color.r() as usize],
+            self.tables[1][color.g() as usize],
+            self.tables[2][color.b() as usize],
+        )
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Calibration::new([1.0; 3], [1.0; 3])
+    }
+}
+
+// Every color written to the display passes through here. The calibration is only applied when
+// a frame goes out to the panel, the other outputs show colors as they are meant to look.
+#[derive(Default)]
+pub struct ColorPipeline {
+    pub night_shift: NightShift,
+    pub calibration: Calibration,
+    pub brightness: Brightness,
+}
+
+impl ColorPipeline {
+    pub fn apply(&self, color: Rgb888) -> Rgb888 {
+        self.brightness.apply(self.night_shift.apply(color))
+    }
+
+    // Returns whether the time based stages changed the output.
+    pub fn update(&mut self, now: DateTime<Local>, instant: Instant) -> bool {
+        let brightness_changed = self.brightness.update(now.time(), instant);
+        let night_shift_changed = self.night_shift.update(now);
+
+        brightness_changed || night_shift_changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+
+    use super::{Calibration, ColorPipeline};
+
+    #[test]
+    fn default_calibration_keeps_colors() {
+        let calibration = Calibration::default();
+        let color = Rgb888::new(12, 128, 255);
+
+        assert_eq!(calibration.apply(color), color);
+    }
+
+    #[test]
+    fn gamma_darkens_midtones_only() {
+        let calibration = Calibration::new([2.2; 3], [1.0; 3]);
+
+        assert_eq!(calibration.apply(Rgb888::BLACK), Rgb888::BLACK);
+        assert_eq!(calibration.apply(Rgb888::WHITE), Rgb888::WHITE);
+        assert_eq!(calibration.apply(Rgb888::new(128, 128, 128)).r(), 56);
+    }
+
+    #[test]
+    fn gains_balance_the_channels() {
+        let calibration = Calibration::new([1.0; 3], [1.0, 0.8, 0.5]);
+
+        assert_eq!(calibration.apply(Rgb888::WHITE), Rgb888::new(255, 204, 128));
+    }
+
+    #[test]
+    fn leaves_calibration_to_the_panel() {
+        let pipeline = ColorPipeline {
+            calibration: Calibration::new([2.2; 3], [1.0, 0.8, 0.5]),
+            ..Default::default()
+        };
+        let color = Rgb888::new(128, 128, 128);
+
+        assert_eq!(pipeline.apply(color), color);
+    }
+}
@@ -0,0 +1,508 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::Arc;
+
+use embedded_graphics::mono_font::{iso_8859_14::*, MonoFont, MonoTextStyle};
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Text;
+
+// The fonts that come with embedded-graphics, named by the size of their characters. The first
+// one is the default.
+static BUILTIN: [(&str, MonoFont<'static>); 22] = [
+    ("4x6", FONT_4X6),
+    ("5x7", FONT_5X7),
+    ("5x8", FONT_5X8),
+    ("6x9", FONT_6X9),
+    ("6x10", FONT_6X10),
+    ("6x12", FONT_6X12),
+    ("6x13", FONT_6X13),
+    ("6x13-bold", FONT_6X13_BOLD),
+    ("6x13-italic", FONT_6X13_ITALIC),
+    ("7x13", FONT_7X13),
+    ("7x13-bold", FONT_7X13_BOLD),
+    ("7x13-italic", FONT_7X13_ITALIC),
+    ("7x14", FONT_7X14),
+    ("7x14-bold", FONT_7X14_BOLD),
+    ("8x13", FONT_8X13),
+    ("8x13-bold", FONT_8X13_BOLD),
+    ("8x13-italic", FONT_8X13_ITALIC),
+    ("9x15", FONT_9X15),
+    ("9x15-bold", FONT_9X15_BOLD),
+    ("9x18", FONT_9X18),
+    ("9x18-bold", FONT_9X18_BOLD),
+    ("10x20", FONT_10X20),
+];
+
+#[derive(Debug)]
+pub enum FontError {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, usize, String),
+    Unknown(String),
+    Builtin(PathBuf),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontError::Read(path, e) => write!(f, "couldn't read {}: {}", path.display(), e),
+            FontError::Parse(path, line, message) => {
+                write!(
+                    f,
+                    "invalid font {} line {}: {}",
+                    path.display(),
+                    line,
+                    message
+                )
+            }
+            FontError::Unknown(name) => write!(
+                f,
+                "unknown font '{}', expected a size like 6x10 or the name of a BDF file",
+                name
+            ),
+            FontError::Builtin(path) => write!(
+                f,
+                "{} is named like a built-in font, rename it to use it",
+                path.display()
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Font {
+    Mono(&'static MonoFont<'static>),
+    Bdf(Arc<BdfFont>),
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Font::Mono(&BUILTIN[0].1)
+    }
+}
+
+impl PartialEq for Font {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Font::Mono(a), Font::Mono(b)) => ptr::eq(*a, *b),
+            (Font::Bdf(a), Font::Bdf(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Font {
+    // Rows from the top of a line down to and including the baseline.
+    pub fn ascent(&self) -> u32 {
+        match self {
+            Font::Mono(font) => font.baseline + 1,
+            Font::Bdf(font) => font.ascent,
+        }
+    }
+
+    pub fn line_height(&self) -> u32 {
+        match self {
+            Font::Mono(font) => font.character_size.height,
+            Font::Bdf(font) => font.ascent + font.descent,
+        }
+    }
+
+    pub fn text_width(&self, text: &str) -> u32 {
+        match self {
+            Font::Mono(font) => {
+                let advance = font.character_size.width + font.character_spacing;
+                (text.chars().count() as u32 * advance).saturating_sub(font.character_spacing)
+            }
+            Font::Bdf(font) => text
+                .chars()
+                .filter_map(|c| font.glyph(c))
+                .map(|glyph| glyph.advance)
+                .sum(),
+        }
+    }
+
+    // Like embedded-graphics, text starts at the left end of its baseline at `point`.
+    pub fn bounding_box(&self, text: &str, point: Point) -> Rectangle {
+        Rectangle::new(
+            Point::new(point.x, point.y - self.ascent() as i32 + 1),
+            Size::new(self.text_width(text), self.line_height()),
+        )
+    }
+
+    pub fn draw<D: DrawTarget<Color = Rgb888>>(
+        &self,
+        text: &str,
+        point: Point,
+        color: Rgb888,
+        target: &mut D,
+    ) {
+        match self {
+            Font::Mono(font) => {
+                Text::new(text, point, MonoTextStyle::new(font, color))
+                    .draw(target)
+                    .ok();
+            }
+            Font::Bdf(font) => font.draw(text, point, color, target),
+        }
+    }
+}
+
+// A pixel font in the Glyph Bitmap Distribution Format, like the X11 fonts.
+#[derive(Debug)]
+pub struct BdfFont {
+    glyphs: HashMap<char, Glyph>,
+    default_char: Option<char>,
+    ascent: u32,
+    descent: u32,
+}
+
+#[derive(Default, Debug)]
+struct Glyph {
+    advance: u32,
+    size: Size,
+    // From the left end of the baseline to the bottom left corner of the bitmap, y points up.
+    offset: Point,
+    pixels: Vec<bool>,
+}
+
+impl BdfFont {
+    pub fn load(path: &Path) -> Result<BdfFont, FontError> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| FontError::Read(path.to_path_buf(), e))?;
+
+        BdfFont::parse(&contents)
+            .map_err(|(line, message)| FontError::Parse(path.to_path_buf(), line, message))
+    }
+
+    // Only what is needed to draw is read, the ascent and descent fall back to the font bounding
+    // box and characters without an encoding are left out.
+    fn parse(contents: &str) -> Result<BdfFont, (usize, String)> {
+        let mut glyphs = HashMap::new();
+        let mut default_char = None;
+        let (mut ascent, mut descent) = (None, None);
+        let mut bounding_box = None;
+        let mut glyph: Option<(Option<char>, Glyph)> = None;
+
+        let mut lines = contents
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()));
+        while let Some((number, line)) = lines.next() {
+            let (keyword, values) = line.split_once(' ').unwrap_or((line, ""));
+            match (keyword, glyph.as_mut()) {
+                ("FONTBOUNDINGBOX", _) => bounding_box = Some(numbers::<4>(number, values)?),
+                ("FONT_ASCENT", _) => ascent = Some(numbers::<1>(number, values)?[0]),
+                ("FONT_DESCENT", _) => descent = Some(numbers::<1>(number, values)?[0]),
+                ("DEFAULT_CHAR", _) => {
+                    default_char = char::from_u32(numbers::<1>(number, values)?[0] as u32)
+                }
+                ("STARTCHAR", _) => glyph = Some((None, Glyph::default())),
+                ("ENCODING", Some((encoding, _))) => {
+                    let code = numbers::<1>(number, values)?[0];
+                    *encoding = u32::try_from(code).ok().and_then(char::from_u32);
+                }
+                ("DWIDTH", Some((_, glyph))) => {
+                    glyph.advance = numbers::<1>(number, values)?[0].max(0) as u32
+                }
+                ("BBX", Some((_, glyph))) => {
+                    let [width, height, x, y] = numbers::<4>(number, values)?;
+                    if width < 0 || height < 0 {
+                        return Err((number, format!("negative glyph size '{}'", values)));
+                    }
+                    glyph.size = Size::new(width as u32, height as u32);
+                    glyph.offset = Point::new(x, y);
+                }
+                ("BITMAP", Some((_, glyph))) => {
+                    for _ in 0..glyph.size.height {
+                        let (number, row) = lines
+                            .next()
+                            .ok_or((number, "the bitmap ends early".to_string()))?;
+                        glyph
+                            .pixels
+                            .extend(bitmap_row(number, row, glyph.size.width)?);
+                    }
+                }
+                ("ENDCHAR", Some(_)) => {
+                    if let Some((Some(c), glyph)) = glyph.take() {
+                        glyphs.insert(c, glyph);
+                    }
+                }
+                ("ENCODING" | "DWIDTH" | "BBX" | "BITMAP" | "ENDCHAR", None) => {
+                    return Err((number, format!("{} outside of a character", keyword)))
+                }
+                _ => (),
+            }
+        }
+
+        let end = contents.lines().count();
+        let [_, height, _, y] = bounding_box.ok_or((end, "no FONTBOUNDINGBOX".to_string()))?;
+        let ascent = ascent.unwrap_or(height + y).max(0) as u32;
+        let descent = descent.unwrap_or(-y).max(0) as u32;
+
+        Ok(BdfFont {
+            glyphs,
+            default_char,
+            ascent,
+            descent,
+        })
+    }
+
+    // Missing characters show as the default character of the font, or as a question mark.
+    fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs
+            .get(&c)
+            .or_else(|| self.default_char.and_then(|c| self.glyphs.get(&c)))
+            .or_else(|| self.glyphs.get(&'?'))
+    }
+
+    fn draw<D: DrawTarget<Color = Rgb888>>(
+        &self,
+        text: &str,
+        point: Point,
+        color: Rgb888,
+        target: &mut D,
+    ) {
+        let mut x = point.x;
+        let pixels = text
+            .chars()
+            .filter_map(|c| self.glyph(c))
+            .flat_map(|glyph| {
+                let top_left = Point::new(
+                    x + glyph.offset.x,
+                    point.y - glyph.offset.y - glyph.size.height as i32 + 1,
+                );
+                x += glyph.advance as i32;
+
+                Rectangle::new(top_left, glyph.size)
+                    .points()
+                    .zip(&glyph.pixels)
+                    .filter(|(_, &on)| on)
+                    .map(move |(p, _)| Pixel(p, color))
+            });
+
+        target.draw_iter(pixels).ok();
+    }
+}
+
+fn numbers<const N: usize>(line: usize, values: &str) -> Result<[i32; N], (usize, String)> {
+    values
+        .split_whitespace()
+        .map(|value| value.parse().ok())
+        .collect::<Option<Vec<i32>>>()
+        .and_then(|numbers| numbers.get(..N).and_then(|n| n.try_into().ok()))
+        .ok_or_else(|| (line, format!("expected {} numbers, got '{}'", N, values)))
+}
+
+// Rows are hex digits padded to whole bytes, the highest bit of the first byte is the leftmost
+// pixel.
+fn bitmap_row(line: usize, row: &str, width: u32) -> Result<Vec<bool>, (usize, String)> {
+    let bytes = (0..row.len() / 2)
+        .map(|i| {
+            row.get(i * 2..i * 2 + 2)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>()
+        .filter(|bytes| bytes.len() * 8 >= width as usize)
+        .ok_or_else(|| {
+            (
+                line,
+                format!("'{}' isn't a bitmap row {} pixels wide", row, width),
+            )
+        })?;
+
+    Ok((0..width as usize)
+        .map(|x| bytes[x / 8] & (0x80 >> (x % 8)) != 0)
+        .collect())
+}
+
+// Fonts by name, the built-in ones and BDF fonts from disk by their file name.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FontRegistry {
+    fonts: HashMap<String, Font>,
+}
+
+impl FontRegistry {
+    pub fn builtin() -> Self {
+        FontRegistry {
+            fonts: BUILTIN
+                .iter()
+                .map(|(name, font)| (name.to_string(), Font::Mono(font)))
+                .collect(),
+        }
+    }
+
+    pub fn load_dir(&mut self, dir: &Path) -> Result<(), FontError> {
+        let entries = fs::read_dir(dir).map_err(|e| FontError::Read(dir.to_path_buf(), e))?;
+        for entry in entries {
+            let path = entry
+                .map_err(|e| FontError::Read(dir.to_path_buf(), e))?
+                .path();
+            let is_bdf = path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("bdf"));
+            if let (true, Some(name)) = (is_bdf, path.file_stem()) {
+                let name = name.to_string_lossy().into_owned();
+                // Replacing a built-in font would quietly change text that asked for its size.
+                if BUILTIN.iter().any(|(builtin, _)| *builtin == name) {
+                    return Err(FontError::Builtin(path));
+                }
+                let font = Font::Bdf(Arc::new(BdfFont::load(&path)?));
+                self.fonts.insert(name, font);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Result<Font, FontError> {
+        self.fonts
+            .get(name)
+            .cloned()
+            .ok_or_else(|| FontError::Unknown(name.to_string()))
+    }
+}
+
+// The fonts apps draw with, the clock of the idle screen is large enough to read across a room.
+// Backgrounds pick theirs by name from `registry`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Fonts {
+    pub clock: Font,
+    pub title: Font,
+    pub registry: FontRegistry,
+}
+
+impl Default for Fonts {
+    fn default() -> Self {
+        let registry = FontRegistry::builtin();
+
+        Fonts {
+            clock: registry.get("10x20").unwrap(),
+            title: Font::default(),
+            registry,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use embedded_graphics::geometry::{Point, Size};
+    use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+    use embedded_graphics::primitives::Rectangle;
+
+    use super::{BdfFont, Font, FontError, FontRegistry};
+    use crate::pixel_display::framebuffer::Framebuffer;
+    use crate::test_util;
+
+    // A 3x5 font with a digit, and a question mark for everything else.
+    const TINY: &str = "\
+STARTFONT 2.1
+FONT -misc-tiny-medium-r-normal--5-50-75-75-c-40-iso10646-1
+SIZE 5 75 75
+FONTBOUNDINGBOX 3 5 0 -1
+STARTPROPERTIES 2
+FONT_ASCENT 5
+FONT_DESCENT 1
+ENDPROPERTIES
+CHARS 2
+STARTCHAR one
+ENCODING 49
+SWIDTH 800 0
+DWIDTH 4 0
+BBX 2 5 1 0
+BITMAP
+40
+C0
+40
+40
+40
+ENDCHAR
+STARTCHAR question
+ENCODING 63
+SWIDTH 800 0
+DWIDTH 4 0
+BBX 3 3 0 2
+BITMAP
+E0
+20
+40
+ENDCHAR
+ENDFONT
+";
+
+    #[test]
+    fn draws_bdf_glyphs_on_the_baseline() {
+        let font = Font::Bdf(BdfFont::parse(TINY).unwrap().into());
+        let mut layer = Framebuffer::new(Size::new(12, 8));
+        font.draw("1x", Point::new(0, 5), Rgb888::WHITE, &mut layer);
+
+        let lit: Vec<_> = layer
+            .pixels()
+            .iter()
+            .enumerate()
+            .filter(|(_, &color)| color == Rgb888::WHITE)
+            .map(|(i, _)| Point::new(i as i32 % 12, i as i32 / 12))
+            .collect();
+        assert_eq!(
+            lit,
+            [
+                Point::new(2, 1),
+                Point::new(4, 1),
+                Point::new(5, 1),
+                Point::new(6, 1),
+                Point::new(1, 2),
+                Point::new(2, 2),
+                Point::new(6, 2),
+                Point::new(2, 3),
+                Point::new(5, 3),
+                Point::new(2, 4),
+                Point::new(2, 5),
+            ]
+        );
+        assert_eq!(font.text_width("1x"), 8);
+        assert_eq!(
+            font.bounding_box("1x", Point::new(0, 5)),
+            Rectangle::new(Point::new(0, 1), Size::new(8, 6))
+        );
+    }
+
+    #[test]
+    fn reports_broken_fonts_with_their_line() {
+        let broken = TINY.replace("BBX 2 5 1 0", "BBX 2 five 1 0");
+        assert_eq!(BdfFont::parse(&broken).unwrap_err().0, 14);
+
+        let short = TINY.replace("C0\n40\n40\n40\nENDCHAR", "C0\nENDCHAR");
+        assert!(BdfFont::parse(&short).is_err());
+    }
+
+    #[test]
+    fn finds_fonts_by_name() {
+        let dir = test_util::temp_dir();
+        fs::write(dir.join("tiny.bdf"), TINY).unwrap();
+        let mut registry = FontRegistry::builtin();
+        registry.load_dir(&dir).unwrap();
+
+        assert_eq!(registry.get("tiny").unwrap().line_height(), 6);
+        assert_eq!(registry.get("10x20").unwrap().text_width("12:34"), 50);
+        assert_eq!(registry.get("4x6").unwrap(), Font::default());
+        assert!(registry.get("comic-sans").is_err());
+    }
+
+    #[test]
+    fn keeps_the_builtin_fonts() {
+        let dir = test_util::temp_dir();
+        fs::write(dir.join("6x10.bdf"), TINY).unwrap();
+        let mut registry = FontRegistry::builtin();
+
+        assert!(matches!(
+            registry.load_dir(&dir),
+            Err(FontError::Builtin(_))
+        ));
+        assert_eq!(registry.get("6x10").unwrap().line_height(), 10);
+    }
+}
@@ -0,0 +1,213 @@
+use std::convert::Infallible;
+#[cfg(test)]
+use std::path::Path;
+
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*};
+#[cfg(test)]
+use image::{ImageResult, RgbImage};
+
+use super::surface::Surface;
+
+// An off-screen layer, drawn pixels are opaque and the rest keeps the alpha it was created with.
+#[derive(PartialEq, Eq)]
+pub struct Framebuffer {
+    size: Size,
+    pixels: Vec<Rgb888>,
+    alpha: Vec<u8>,
+}
+
+impl Framebuffer {
+    pub fn new(size: Size) -> Self {
+        Framebuffer::with_alpha(size, u8::MAX)
+    }
+
+    pub fn transparent(size: Size) -> Self {
+        Framebuffer::with_alpha(size, 0)
+    }
+
+    // A transparent layer over `backdrop`, what is drawn on it can see the colors it will cover.
+    pub fn over(backdrop: &Framebuffer) -> Self {
+        Framebuffer {
+            pixels: backdrop.pixels.clone(),
+            ..Framebuffer::transparent(backdrop.size)
+        }
+    }
+
+    fn with_alpha(size: Size, alpha: u8) -> Self {
+        let len = (size.width * size.height) as usize;
+
+        Framebuffer {
+            size,
+            pixels: vec![Rgb888::BLACK; len],
+            alpha: vec![alpha; len],
+        }
+    }
+
+    pub fn pixel(&self, point: Point) -> Option<Rgb888> {
+        self.index(point).map(|i| self.pixels[i])
+    }
+
+    // Every pixel with its position and alpha.
+    pub fn layer_pixels(&self) -> impl Iterator<Item = (Point, Rgb888, u8)> + '_ {
+        let width = self.size.width as i32;
+        self.pixels
+            .iter()
+            .zip(&self.alpha)
+            .enumerate()
+            .map(move |(i, (&color, &alpha))| {
+                let i = i as i32;
+                (Point::new(i % width, i / width), color, alpha)
+            })
+    }
+
+    pub fn pixels(&self) -> &[Rgb888] {
+        &self.pixels
+    }
+
+    #[cfg(test)]
+    pub fn to_image(&self) -> RgbImage {
+        RgbImage::from_fn(self.size.width, self.size.height, |x, y| {
+            let color = self.pixels[(y * self.size.width + x) as usize];
+            image::Rgb([color.r(), color.g(), color.b()])
+        })
+    }
+
+    #[cfg(test)]
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        self.to_image()
+            .save_with_format(path, image::ImageFormat::Png)
+    }
+
+    fn index(&self, point: Point) -> Option<usize> {
+        if point.x < 0
+            || point.y < 0
+            || point.x as u32 >= self.size.width
+            || point.y as u32 >= self.size.height
+        {
+            return None;
+        }
+
+        Some((point.y as u32 * self.size.width + point.x as u32) as usize)
+    }
+}
+
+impl OriginDimensions for Framebuffer {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl DrawTarget for Framebuffer {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let Some(i) = self.index(point) {
+                self.pixels[i] = color;
+                self.alpha[i] = u8::MAX;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.pixels.fill(color);
+        self.alpha.fill(u8::MAX);
+        Ok(())
+    }
+}
+
+impl Surface for Framebuffer {
+    fn blit(&mut self, layer: &Framebuffer, position: Point, opacity: u8) {
+        for (point, color, alpha) in layer.layer_pixels() {
+            let alpha = scale_alpha(alpha, opacity);
+            if let Some(i) = self.index(point + position).filter(|_| alpha > 0) {
+                // Colors are kept unpremultiplied, where this layer is see-through the color
+                // under it counts for less, and not at all on a transparent pixel.
+                let combined = alpha + scale_alpha(self.alpha[i], u8::MAX - alpha);
+                let weight = (alpha as u16 * 255 + combined as u16 / 2) / combined as u16;
+                self.pixels[i] = blend(self.pixels[i], color, weight as u8);
+                self.alpha[i] = combined;
+            }
+        }
+    }
+
+    fn pixel(&self, point: Point) -> Option<Rgb888> {
+        Framebuffer::pixel(self, point)
+    }
+}
+
+pub fn scale_alpha(alpha: u8, opacity: u8) -> u8 {
+    (alpha as u16 * opacity as u16 / 255) as u8
+}
+
+pub fn blend(under: Rgb888, over: Rgb888, alpha: u8) -> Rgb888 {
+    let channel = |under: u8, over: u8| {
+        ((over as u16 * alpha as u16 + under as u16 * (255 - alpha as u16) + 127) / 255) as u8
+    };
+
+    Rgb888::new(
+        channel(under.r(), over.r()),
+        channel(under.g(), over.g()),
+        channel(under.b(), over.b()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{pixelcolor::Rgb888, prelude::*};
+
+    use super::Framebuffer;
+    use crate::pixel_display::snapshot;
+    use crate::pixel_display::surface::Surface;
+
+    #[test]
+    fn blits_only_drawn_pixels_of_transparent_layers() {
+        let mut frame = Framebuffer::new(Size::new(4, 1));
+        frame.clear(Rgb888::BLUE).unwrap();
+        let mut layer = Framebuffer::transparent(Size::new(2, 1));
+        Pixel(Point::new(1, 0), Rgb888::RED)
+            .draw(&mut layer)
+            .unwrap();
+
+        frame.blit(&layer, Point::new(1, 0), u8::MAX);
+
+        let row: Vec<_> = (0..4)
+            .map(|x| frame.pixel(Point::new(x, 0)).unwrap())
+            .collect();
+        assert_eq!(row, [Rgb888::BLUE, Rgb888::BLUE, Rgb888::RED, Rgb888::BLUE]);
+    }
+
+    #[test]
+    fn blends_by_opacity() {
+        let mut frame = Framebuffer::new(Size::new(1, 1));
+        let mut layer = Framebuffer::new(Size::new(1, 1));
+        layer.clear(Rgb888::WHITE).unwrap();
+
+        frame.blit(&layer, Point::zero(), 128);
+
+        assert_eq!(frame.pixel(Point::zero()), Some(Rgb888::new(128, 128, 128)));
+    }
+
+    #[test]
+    fn blends_half_opaque_layers_once() {
+        let mut white = Framebuffer::new(Size::new(1, 1));
+        white.clear(Rgb888::WHITE).unwrap();
+        let mut layer = Framebuffer::transparent(Size::new(1, 1));
+        layer.blit(&white, Point::zero(), 128);
+
+        let mut display = snapshot::display();
+        display.blit(&layer, Point::zero(), u8::MAX);
+
+        assert_eq!(layer.pixel(Point::zero()), Some(Rgb888::WHITE));
+        assert_eq!(
+            display.pixel(Point::zero()),
+            Some(Rgb888::new(128, 128, 128))
+        );
+    }
+}
@@ -24,18 +24,15 @@ impl InputHandler {
             let mut last_sw_state = Level::High;
 
             loop {
-                match gpio.get(GPIO_PIN_CLK) {
-                    Ok(p) => {
-                        if last_clk_state != p.read() {
-                            if gpio.get(GPIO_PIN_DAT).unwrap().read() != p.read() {
-                                tx.send(Input::Next).unwrap();
-                            } else {
-                                tx.send(Input::Prev).unwrap();
-                            }
+                if let Ok(p) = gpio.get(GPIO_PIN_CLK) {
+                    if last_clk_state != p.read() {
+                        if gpio.get(GPIO_PIN_DAT).unwrap().read() != p.read() {
+                            tx.send(Input::Next).unwrap();
+                        } else {
+                            tx.send(Input::Prev).unwrap();
                         }
-                        last_clk_state = p.read()
                     }
-                    Err(_) => {}
+                    last_clk_state = p.read()
                 }
 
                 let state = gpio.get(GPIO_PIN_SW).unwrap().read();
@@ -44,6 +41,8 @@ impl InputHandler {
                 } else if last_sw_state == Level::Low && state == Level::High {
                     if pressed.elapsed().as_millis() > 75 && pressed.elapsed().as_millis() < 500 {
                         tx.send(Input::Pressed).unwrap();
+                    } else if (500..=1000).contains(&pressed.elapsed().as_millis()) {
+                        tx.send(Input::Brightness).unwrap();
                     } else if pressed.elapsed().as_millis() > 1000 {
                         tx.send(Input::Held).unwrap();
                     }
@@ -1,3 +1,17 @@
+pub mod brightness;
+pub mod color;
+pub mod font;
+pub mod framebuffer;
 #[cfg(feature = "real")]
 pub mod input_handler;
+pub mod night_shift;
+pub mod orientation;
+#[allow(clippy::module_inception)]
 pub mod pixel_display;
+pub mod power_limit;
+#[cfg(test)]
+pub mod snapshot;
+pub mod surface;
+pub mod terminal;
+pub mod text_layout;
+pub mod text_style;
@@ -0,0 +1,242 @@
+use std::f64::consts::PI;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeZone, Timelike, Utc};
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+
+const SECONDS_PER_DAY: f32 = 86_400.0;
+
+pub enum NightSchedule {
+    Fixed { start: NaiveTime, end: NaiveTime },
+    // Night lasts from sunset to sunrise at this location.
+    Sun { latitude: f64, longitude: f64 },
+}
+
+impl NightSchedule {
+    fn night_at(&self, now: DateTime<Local>) -> Option<(NaiveTime, NaiveTime)> {
+        match *self {
+            NightSchedule::Fixed { start, end } => Some((start, end)),
+            NightSchedule::Sun {
+                latitude,
+                longitude,
+            } => {
+                let (sunrise, sunset) = sun_times(now.date_naive(), latitude, longitude)?;
+                Some((
+                    sunset.with_timezone(&Local).time(),
+                    sunrise.with_timezone(&Local).time(),
+                ))
+            }
+        }
+    }
+}
+
+// Warms up the colors at night, ramping in and out over `transition` after the start and end.
+pub struct NightShift {
+    schedule: Option<NightSchedule>,
+    gains: [f32; 3],
+    transition: Duration,
+    amount: f32,
+}
+
+impl Default for NightShift {
+    fn default() -> Self {
+        NightShift {
+            schedule: None,
+            gains: [1.0; 3],
+            transition: Duration::ZERO,
+            amount: 0.0,
+        }
+    }
+}
+
+impl NightShift {
+    pub fn new(schedule: NightSchedule, temperature: u32, transition: Duration) -> Self {
+        NightShift {
+            schedule: Some(schedule),
+            gains: temperature_gains(temperature),
+            transition,
+            amount: 0.0,
+        }
+    }
+
+    // Returns whether the shift changed enough to be visible.
+    pub fn update(&mut self, now: DateTime<Local>) -> bool {
+        let night = self
+            .schedule
+            .as_ref()
+            .and_then(|schedule| schedule.night_at(now));
+        let previous = self.step();
+
+        self.amount = match night {
+            Some((start, end)) => self.amount_at(now.time(), start, end),
+            None => 0.0,
+        };
+
+        self.step() != previous
+    }
+
+    pub fn apply(&self, color: Rgb888) -> Rgb888 {
+        let channel = |c: u8, gain: f32| {
+            let gain = 1.0 - self.amount * (1.0 - gain);
+            (c as f32 * gain).round() as u8
+        };
+
+        Rgb888::new(
+            channel(color.r(), self.gains[0]),
+            channel(color.g(), self.gains[1]),
+            channel(color.b(), self.gains[2]),
+        )
+    }
+
+    fn amount_at(&self, time: NaiveTime, start: NaiveTime, end: NaiveTime) -> f32 {
+        let since_start = seconds_between(start, time);
+        let night = seconds_between(start, end);
+        let transition = self.transition.as_secs_f32();
+        let ramp = |elapsed: f32| {
+            if transition == 0.0 {
+                1.0
+            } else {
+                (elapsed / transition).min(1.0)
+            }
+        };
+
+        if since_start < night {
+            ramp(since_start)
+        } else {
+            ramp(night) * (1.0 - ramp(since_start - night))
+        }
+    }
+
+    fn step(&self) -> u8 {
+        (self.amount * 255.0).round() as u8
+    }
+}
+
+// Seconds from `from` until the next time the clock shows `to`.
+fn seconds_between(from: NaiveTime, to: NaiveTime) -> f32 {
+    let seconds = to.num_seconds_from_midnight() as f32 - from.num_seconds_from_midnight() as f32;
+    seconds.rem_euclid(SECONDS_PER_DAY)
+}
+
+// Relative channel output of a black body at `kelvin`, after Tanner Helland's approximation.
+fn temperature_gains(kelvin: u32) -> [f32; 3] {
+    let t = kelvin as f32 / 100.0;
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.69873 * (t - 60.0).powf(-0.13320476)
+    };
+    let green = if t <= 66.0 {
+        99.4708 * t.ln() - 161.11957
+    } else {
+        288.12216 * (t - 60.0).powf(-0.07551485)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.51773 * (t - 10.0).ln() - 305.0448
+    };
+
+    [red, green, blue].map(|c| (c / 255.0).clamp(0.0, 1.0))
+}
+
+// Sunrise and sunset following the sunrise equation, None during polar day or night.
+pub fn sun_times(
+    date: NaiveDate,
+    latitude: f64,
+    longitude: f64,
+) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let radians = |degrees: f64| degrees * PI / 180.0;
+
+    // Days since noon on 2000-01-01, the J2000 epoch.
+    let days = (date.num_days_from_ce() - 730_120) as f64 + 0.0008;
+    let mean_solar_noon = days - longitude / 360.0;
+    let anomaly = (357.5291 + 0.98560028 * mean_solar_noon).rem_euclid(360.0);
+    let center = 1.9148 * radians(anomaly).sin()
+        + 0.02 * radians(2.0 * anomaly).sin()
+        + 0.0003 * radians(3.0 * anomaly).sin();
+    let ecliptic_longitude = (anomaly + center + 180.0 + 102.9372).rem_euclid(360.0);
+    let transit = 2_451_545.0 + mean_solar_noon + 0.0053 * radians(anomaly).sin()
+        - 0.0069 * radians(2.0 * ecliptic_longitude).sin();
+    let declination = (radians(ecliptic_longitude).sin() * radians(23.44).sin()).asin();
+    let hour_angle = (radians(-0.833).sin() - radians(latitude).sin() * declination.sin())
+        / (radians(latitude).cos() * declination.cos());
+    if !(-1.0..=1.0).contains(&hour_angle) {
+        return None;
+    }
+    let half_day = hour_angle.acos() * 180.0 / PI / 360.0;
+
+    let to_utc = |julian: f64| {
+        let seconds = ((julian - 2_440_587.5) * 86_400.0).round() as i64;
+        Utc.timestamp_opt(seconds, 0).single()
+    };
+
+    Some((to_utc(transit - half_day)?, to_utc(transit + half_day)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{Local, NaiveDate, NaiveTime, TimeZone, Timelike};
+    use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+
+    use super::{sun_times, NightSchedule, NightShift};
+
+    fn fixed_shift() -> NightShift {
+        NightShift::new(
+            NightSchedule::Fixed {
+                start: NaiveTime::from_hms_opt(21, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+            },
+            2000,
+            Duration::from_secs(30 * 60),
+        )
+    }
+
+    fn at(hour: u32, minute: u32) -> chrono::DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 4, 5, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn keeps_colors_during_the_day() {
+        let mut shift = fixed_shift();
+        shift.update(at(12, 0));
+
+        assert_eq!(shift.apply(Rgb888::WHITE), Rgb888::WHITE);
+    }
+
+    #[test]
+    fn warms_up_gradually_at_night() {
+        let mut shift = fixed_shift();
+
+        assert!(shift.update(at(21, 15)));
+        let evening = shift.apply(Rgb888::WHITE);
+        assert_eq!(evening.r(), 255);
+        assert!(evening.b() < 255);
+
+        shift.update(at(2, 0));
+        let night = shift.apply(Rgb888::WHITE);
+        assert!(night.g() < evening.g());
+        assert!(night.b() < evening.b());
+
+        shift.update(at(7, 15));
+        assert!(shift.apply(Rgb888::WHITE).b() > night.b());
+        shift.update(at(7, 30));
+        assert_eq!(shift.apply(Rgb888::WHITE), Rgb888::WHITE);
+    }
+
+    #[test]
+    fn finds_sunrise_and_sunset() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        let (sunrise, sunset) = sun_times(date, 52.37, 4.9).unwrap();
+
+        // Amsterdam, 05:18 and 22:06 local summer time.
+        let minutes = |time: chrono::DateTime<chrono::Utc>| time.hour() * 60 + time.minute();
+        assert!(minutes(sunrise).abs_diff(3 * 60 + 18) <= 3);
+        assert!(minutes(sunset).abs_diff(20 * 60 + 6) <= 3);
+        assert!(sun_times(date, 80.0, 0.0).is_none());
+    }
+}
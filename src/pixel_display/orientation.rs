@@ -0,0 +1,146 @@
+use embedded_graphics::{prelude::*, primitives::Rectangle};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Clockwise270,
+}
+
+impl Rotation {
+    pub fn from_degrees(degrees: u32) -> Option<Rotation> {
+        match degrees {
+            0 => Some(Rotation::None),
+            90 => Some(Rotation::Clockwise90),
+            180 => Some(Rotation::Clockwise180),
+            270 => Some(Rotation::Clockwise270),
+            _ => None,
+        }
+    }
+}
+
+// How the panel is mounted. Apps draw on the logical display, which is flipped and then
+// rotated clockwise onto the physical panel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Orientation {
+    pub rotation: Rotation,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+impl Orientation {
+    pub fn is_identity(&self) -> bool {
+        *self == Orientation::default()
+    }
+
+    pub fn logical_size(&self, physical: Size) -> Size {
+        match self.rotation {
+            Rotation::Clockwise90 | Rotation::Clockwise270 => {
+                Size::new(physical.height, physical.width)
+            }
+            _ => physical,
+        }
+    }
+
+    pub fn to_physical(self, point: Point, physical: Size) -> Point {
+        let logical = self.logical_size(physical);
+        let width = logical.width as i32;
+        let height = logical.height as i32;
+
+        let x = if self.flip_horizontal {
+            width - 1 - point.x
+        } else {
+            point.x
+        };
+        let y = if self.flip_vertical {
+            height - 1 - point.y
+        } else {
+            point.y
+        };
+
+        match self.rotation {
+            Rotation::None => Point::new(x, y),
+            Rotation::Clockwise90 => Point::new(height - 1 - y, x),
+            Rotation::Clockwise180 => Point::new(width - 1 - x, height - 1 - y),
+            Rotation::Clockwise270 => Point::new(y, width - 1 - x),
+        }
+    }
+
+    pub fn rectangle_to_physical(&self, area: &Rectangle, physical: Size) -> Rectangle {
+        match area.bottom_right() {
+            Some(bottom_right) => Rectangle::with_corners(
+                self.to_physical(area.top_left, physical),
+                self.to_physical(bottom_right, physical),
+            ),
+            None => Rectangle::zero(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{prelude::*, primitives::Rectangle};
+
+    use super::{Orientation, Rotation};
+
+    const PHYSICAL: Size = Size::new(64, 32);
+
+    fn rotated(rotation: Rotation) -> Orientation {
+        Orientation {
+            rotation,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn swaps_size_for_portrait_rotations() {
+        assert_eq!(
+            rotated(Rotation::Clockwise90).logical_size(PHYSICAL),
+            Size::new(32, 64)
+        );
+        assert_eq!(
+            rotated(Rotation::Clockwise180).logical_size(PHYSICAL),
+            PHYSICAL
+        );
+    }
+
+    #[test]
+    fn maps_logical_corners_onto_the_panel() {
+        let origin = Point::zero();
+
+        assert_eq!(
+            rotated(Rotation::Clockwise90).to_physical(origin, PHYSICAL),
+            Point::new(63, 0)
+        );
+        assert_eq!(
+            rotated(Rotation::Clockwise180).to_physical(origin, PHYSICAL),
+            Point::new(63, 31)
+        );
+        assert_eq!(
+            rotated(Rotation::Clockwise270).to_physical(origin, PHYSICAL),
+            Point::new(0, 31)
+        );
+
+        let mirrored = Orientation {
+            flip_horizontal: true,
+            flip_vertical: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            mirrored.to_physical(Point::new(1, 2), PHYSICAL),
+            Point::new(62, 29)
+        );
+    }
+
+    #[test]
+    fn maps_rectangles() {
+        let area = Rectangle::new(Point::new(0, 0), Size::new(4, 2));
+
+        assert_eq!(
+            rotated(Rotation::Clockwise90).rectangle_to_physical(&area, PHYSICAL),
+            Rectangle::new(Point::new(62, 0), Size::new(2, 4))
+        );
+    }
+}
@@ -1,101 +1,339 @@
-use embedded_graphics::image::Image;
-use embedded_graphics::mono_font::iso_8859_14::FONT_4X6;
+use std::convert::Infallible;
+use std::iter;
 
-use embedded_graphics::primitives::{Line, PrimitiveStyle, StyledDrawable, Triangle};
-use embedded_graphics::{
-    mono_font::MonoTextStyle, pixelcolor::*, prelude::*, text::Text, Drawable,
-};
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::{pixelcolor::*, prelude::*};
 
 use embedded_graphics::pixelcolor::Rgb888;
+#[cfg(feature = "simulated")]
 use embedded_graphics_simulator::{OutputSettingsBuilder, SimulatorDisplay, Window};
 
-use rpi_led_panel::{Canvas, RGBMatrix, RGBMatrixConfig};
-use tinybmp::Bmp;
+#[cfg(feature = "real")]
+use rpi_led_panel::{Canvas, RGBMatrix};
 
+use super::brightness::BrightnessSchedule;
+use super::color::{Calibration, ColorPipeline};
+use super::framebuffer::{blend, scale_alpha, Framebuffer};
+use super::night_shift::NightShift;
+use super::orientation::Orientation;
+use super::power_limit::{PowerLimiter, PowerStats};
+use super::surface::Surface;
+use super::terminal::TerminalDisplay;
+use crate::clock::Clock;
+use crate::config::PanelConfig;
+
+// The panel wins when built with the simulator too.
+#[cfg_attr(all(feature = "real", feature = "simulated"), allow(dead_code))]
 pub enum DisplayMode {
+    #[cfg(feature = "real")]
     Real,
+    #[cfg(feature = "simulated")]
     Simulated,
+    #[cfg(test)]
+    Headless,
+    Terminal,
 }
 
+// Only one of these exists, so the size of the panel variant doesn't matter.
+#[allow(clippy::large_enum_variant)]
 pub enum DisplayOutput {
+    #[cfg(feature = "real")]
     Real(Canvas, RGBMatrix),
+    #[cfg(feature = "simulated")]
     Simulator(SimulatorDisplay<Rgb888>, Window),
+    Headless,
+    Terminal(TerminalDisplay),
 }
 
+// Drawing goes into `frame` as is, colors are only corrected when it is copied to the output.
 pub struct PixelDisplay {
     pub output: DisplayOutput,
+    frame: Framebuffer,
+    colors: ColorPipeline,
+    orientation: Orientation,
+    power: PowerLimiter,
 }
 
 impl PixelDisplay {
-    pub fn draw_text(&mut self, text_str: &str, point: Point) {
-        let text = Text::new(
-            text_str,
-            point,
-            MonoTextStyle::new(&FONT_4X6, Rgb888::WHITE),
-        );
+    pub fn update(&mut self) {
+        self.clear(Rgb888::BLACK).ok();
+    }
+
+    pub fn present(&mut self) {
+        let area = self.frame.bounding_box();
 
         match self.output {
-            DisplayOutput::Real(ref mut c, _) => {
-                text.draw(c).ok();
+            #[cfg(feature = "real")]
+            DisplayOutput::Real(ref mut c, ref mut m) => {
+                let colors = output_colors(&self.frame, &self.colors, &mut self.power, true);
+                c.fill_contiguous(&area, colors).ok();
+                *c = *m.update_on_vsync(Box::new(c.clone()));
             }
-            DisplayOutput::Simulator(ref mut s, _) => {
-                text.draw(s).ok();
+            #[cfg(feature = "simulated")]
+            DisplayOutput::Simulator(ref mut s, ref mut w) => {
+                let colors = output_colors(&self.frame, &self.colors, &mut self.power, false);
+                s.fill_contiguous(&area, colors).ok();
+                w.update(s);
             }
-        };
+            DisplayOutput::Headless => {
+                output_colors(&self.frame, &self.colors, &mut self.power, false);
+            }
+            DisplayOutput::Terminal(ref mut t) => {
+                let colors = output_colors(&self.frame, &self.colors, &mut self.power, false);
+                t.fill_contiguous(&area, colors).ok();
+                t.update();
+            }
+        }
     }
 
-    pub fn draw_line(&mut self, line: Line, style: PrimitiveStyle<Rgb888>) {
-        match self.output {
-            DisplayOutput::Real(ref mut c, _) => line.draw_styled(&style, c).ok(),
-            DisplayOutput::Simulator(ref mut s, _) => line.draw_styled(&style, s).ok(),
-        };
+    pub fn set_brightness(&mut self, level: u8) {
+        self.colors.brightness.set_level(level);
     }
 
-    pub fn draw_triangle(&mut self, triangle: Triangle, style: PrimitiveStyle<Rgb888>) {
-        match self.output {
-            DisplayOutput::Real(ref mut c, _) => triangle.draw_styled(&style, c).ok(),
-            DisplayOutput::Simulator(ref mut s, _) => triangle.draw_styled(&style, s).ok(),
-        };
+    pub fn set_brightness_schedule(&mut self, schedule: BrightnessSchedule) {
+        self.colors.brightness.set_schedule(schedule);
     }
 
-    pub fn draw_image(&mut self, image: Image<'_, Bmp<'_, Rgb888>>, _point: Point) {
-        match self.output {
-            DisplayOutput::Real(ref mut c, _) => image.draw(c).ok(),
-            DisplayOutput::Simulator(ref mut s, _) => image.draw(s).ok(),
-        };
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.colors.calibration = calibration;
     }
 
-    pub fn update(&mut self) {
-        match self.output {
-            DisplayOutput::Real(ref mut c, _) => c.fill(0, 0, 0),
-            DisplayOutput::Simulator(ref mut s, _) => s.clear(Rgb888::BLACK).unwrap(),
-        };
+    pub fn set_night_shift(&mut self, night_shift: NightShift) {
+        self.colors.night_shift = night_shift;
+    }
+
+    // Returns true while the brightness or night shift changes, the frame has to be redrawn.
+    pub fn update_colors(&mut self, clock: &dyn Clock) -> bool {
+        self.colors.update(clock.now(), clock.instant())
+    }
+
+    pub fn set_power_limiter(&mut self, power: PowerLimiter) {
+        self.power = power;
+    }
+
+    pub fn power_stats(&self) -> PowerStats {
+        self.power.stats()
+    }
+
+    // The drawn frame in panel orientation, before any color correction.
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.frame
+    }
+
+    fn physical_size(&self) -> Size {
+        self.frame.size()
+    }
+}
+
+// Apps see the logical display, which is rotated when the panel is mounted sideways.
+impl OriginDimensions for PixelDisplay {
+    fn size(&self) -> Size {
+        self.orientation.logical_size(self.physical_size())
+    }
+}
+
+impl DrawTarget for PixelDisplay {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let orientation = self.orientation;
+        let physical = self.physical_size();
+        let pixels = pixels
+            .into_iter()
+            .map(|Pixel(point, color)| Pixel(orientation.to_physical(point, physical), color));
+
+        self.frame.draw_iter(pixels)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        if !self.orientation.is_identity() {
+            let pixels = area
+                .points()
+                .zip(colors)
+                .map(|(point, color)| Pixel(point, color));
+            return self.draw_iter(pixels);
+        }
+
+        self.frame.fill_contiguous(area, colors)
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = self
+            .orientation
+            .rectangle_to_physical(area, self.physical_size());
+
+        self.frame.fill_solid(&area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.frame.clear(color)
+    }
+}
+
+// Layers are blended with the colors drawn so far, the result is corrected as a whole.
+impl Surface for PixelDisplay {
+    fn blit(&mut self, layer: &Framebuffer, position: Point, opacity: u8) {
+        let physical = self.physical_size();
+        for (point, color, alpha) in layer.layer_pixels() {
+            let alpha = scale_alpha(alpha, opacity);
+            let point = self.orientation.to_physical(point + position, physical);
+            if let Some(under) = self.frame.pixel(point).filter(|_| alpha > 0) {
+                let color = blend(under, color, alpha);
+                self.frame.draw_iter(iter::once(Pixel(point, color))).ok();
+            }
+        }
     }
+
+    // Colors are read back as drawn, so they don't change with the brightness or night shift.
+    fn pixel(&self, point: Point) -> Option<Rgb888> {
+        let size = self.size();
+        if point.x < 0
+            || point.y < 0
+            || point.x as u32 >= size.width
+            || point.y as u32 >= size.height
+        {
+            return None;
+        }
+        self.frame
+            .pixel(self.orientation.to_physical(point, self.physical_size()))
+    }
+}
+
+// The frame with its colors corrected, limited to the power budget. The panel draws current for
+// the calibrated colors, so with `calibrated` those are what the limiter estimates and scales.
+fn output_colors(
+    frame: &Framebuffer,
+    colors: &ColorPipeline,
+    power: &mut PowerLimiter,
+    calibrated: bool,
+) -> Vec<Rgb888> {
+    let corrected: Vec<Rgb888> = frame
+        .pixels()
+        .iter()
+        .map(|&color| match calibrated {
+            true => colors.calibration.apply(colors.apply(color)),
+            false => colors.apply(color),
+        })
+        .collect();
+    power.update(&corrected);
+
+    corrected
+        .into_iter()
+        .map(|color| power.apply(color))
+        .collect()
 }
 
 impl PixelDisplay {
-    pub fn new(rows: u32, cols: u32, display_type: DisplayMode) -> Self {
-        match display_type {
+    pub fn new(panel: &PanelConfig, display_type: DisplayMode) -> Self {
+        let output = match display_type {
+            #[cfg(feature = "real")]
             DisplayMode::Real => {
-                let mut config = RGBMatrixConfig::default();
-                config.rows = rows as usize;
-                config.cols = cols as usize;
                 let (matrix, canvas) =
-                    RGBMatrix::new(config, 0).expect("Matrix initialization failed");
+                    RGBMatrix::new(panel.matrix_config(), 0).expect("Matrix initialization failed");
 
-                PixelDisplay {
-                    output: DisplayOutput::Real(*canvas, matrix),
-                }
+                DisplayOutput::Real(*canvas, matrix)
             }
+            #[cfg(feature = "simulated")]
             DisplayMode::Simulated => {
-                let simulator = SimulatorDisplay::<Rgb888>::new(Size::new(cols, rows));
+                let simulator = SimulatorDisplay::<Rgb888>::new(panel.size());
                 let output_settings = OutputSettingsBuilder::new().scale(10).build();
                 let window = Window::new("Simulator", &output_settings);
 
-                PixelDisplay {
-                    output: DisplayOutput::Simulator(simulator, window),
-                }
+                DisplayOutput::Simulator(simulator, window)
             }
+            #[cfg(test)]
+            DisplayMode::Headless => DisplayOutput::Headless,
+            DisplayMode::Terminal => DisplayOutput::Terminal(TerminalDisplay::new(panel.size())),
+        };
+
+        PixelDisplay {
+            output,
+            frame: Framebuffer::new(panel.size()),
+            colors: ColorPipeline::default(),
+            orientation: panel.orientation,
+            power: PowerLimiter::default(),
         }
     }
+
+    // Renders like the panel but keeps the frame, colors are corrected once it is drawn onto a real display.
+    pub fn offscreen(size: Size) -> Self {
+        PixelDisplay {
+            output: DisplayOutput::Headless,
+            frame: Framebuffer::new(size),
+            colors: ColorPipeline::default(),
+            orientation: Orientation::default(),
+            power: PowerLimiter::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::pixelcolor::Rgb888;
+    use embedded_graphics::prelude::*;
+
+    use super::output_colors;
+    use crate::clock::FakeClock;
+    use crate::pixel_display::color::{Calibration, ColorPipeline};
+    use crate::pixel_display::framebuffer::Framebuffer;
+    use crate::pixel_display::power_limit::PowerLimiter;
+    use crate::pixel_display::snapshot;
+    use crate::pixel_display::surface::Surface;
+
+    #[test]
+    fn reads_back_colors_as_drawn() {
+        let mut display = snapshot::display();
+        display.set_brightness(10);
+        display.update_colors(&FakeClock::new(snapshot::fixed_instant()));
+        let pink = Rgb888::new(255, 105, 180);
+
+        display.clear(pink).ok();
+        display.present();
+
+        assert_eq!(display.pixel(Point::new(3, 2)), Some(pink));
+    }
+
+    #[test]
+    fn calibrates_only_the_panel() {
+        let mut display = snapshot::display();
+        display.set_calibration(Calibration::new([2.2; 3], [1.0, 0.8, 0.5]));
+        let gray = Rgb888::new(128, 128, 128);
+
+        display.clear(gray).ok();
+        display.present();
+
+        assert_eq!(display.framebuffer().pixel(Point::zero()), Some(gray));
+    }
+
+    #[test]
+    fn limits_the_panel_on_its_calibrated_colors() {
+        // Mid-gray is estimated at 2.0 A uncorrected, with gamma 2.2 the LEDs draw less than half.
+        let mut frame = Framebuffer::new(Size::new(64, 32));
+        frame.clear(Rgb888::new(128, 128, 128)).ok();
+        let colors = ColorPipeline {
+            calibration: Calibration::new([2.2; 3], [1.0; 3]),
+            ..Default::default()
+        };
+        let mut power = PowerLimiter::new(Some(1.5), 0.65);
+
+        let uncalibrated = output_colors(&frame, &colors, &mut power, false);
+        assert!(power.stats().scale < 1.0);
+        assert!(uncalibrated[0].r() < 128);
+
+        let calibrated = output_colors(&frame, &colors, &mut power, true);
+        let stats = power.stats();
+        assert!((stats.estimated_amps - 0.88).abs() < 0.01);
+        assert_eq!(stats.scale, 1.0);
+        assert_eq!(
+            calibrated[0],
+            colors.calibration.apply(Rgb888::new(128, 128, 128))
+        );
+    }
 }
@@ -0,0 +1,263 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+
+// A 64x32 panel draws about 4 A at full white, spread over its 6144 LEDs.
+pub const DEFAULT_MILLIAMPS_PER_LED: f32 = 0.65;
+// Frames hovering around the budget would otherwise log a line each.
+const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+const STATS_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct PowerStats {
+    // Current the last frame would have drawn without the limiter.
+    pub estimated_amps: f32,
+    // Factor the last frame was scaled by, 1.0 when it was within the budget.
+    pub scale: f32,
+    pub limited_frames: u64,
+    pub frames: u64,
+}
+
+impl PowerStats {
+    // In the Prometheus text format.
+    pub fn to_metrics(self) -> String {
+        [
+            ("power_estimated_amps", "gauge", self.estimated_amps as f64),
+            ("power_scale", "gauge", self.scale as f64),
+            (
+                "power_limited_frames_total",
+                "counter",
+                self.limited_frames as f64,
+            ),
+            ("frames_total", "counter", self.frames as f64),
+        ]
+        .iter()
+        .map(|(name, kind, value)| {
+            format!(
+                "# TYPE lumi_dash_{0} {1}\nlumi_dash_{0} {2}\n",
+                name, kind, value
+            )
+        })
+        .collect()
+    }
+}
+
+pub struct PowerSettings {
+    pub limiter: PowerLimiter,
+    pub stats_file: Option<PathBuf>,
+}
+
+// Scales frames down when their estimated current goes over the budget of the power supply.
+pub struct PowerLimiter {
+    budget_amps: Option<f32>,
+    milliamps_per_led: f32,
+    stats: PowerStats,
+}
+
+impl Default for PowerLimiter {
+    fn default() -> Self {
+        PowerLimiter::new(None, DEFAULT_MILLIAMPS_PER_LED)
+    }
+}
+
+impl PowerLimiter {
+    pub fn new(budget_amps: Option<f32>, milliamps_per_led: f32) -> Self {
+        PowerLimiter {
+            budget_amps,
+            milliamps_per_led,
+            stats: PowerStats {
+                scale: 1.0,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn stats(&self) -> PowerStats {
+        self.stats
+    }
+
+    // Estimates the current of the frame that is about to be presented and picks its scale.
+    pub fn update(&mut self, pixels: &[Rgb888]) {
+        let sum: u64 = pixels
+            .iter()
+            .map(|c| c.r() as u64 + c.g() as u64 + c.b() as u64)
+            .sum();
+        let estimated_amps = sum as f32 / 255.0 * self.milliamps_per_led / 1000.0;
+
+        let scale = match self.budget_amps {
+            Some(budget) if estimated_amps > budget => budget / estimated_amps,
+            _ => 1.0,
+        };
+
+        self.stats.estimated_amps = estimated_amps;
+        self.stats.scale = scale;
+        self.stats.frames += 1;
+        if scale < 1.0 {
+            self.stats.limited_frames += 1;
+        }
+    }
+
+    pub fn apply(&self, color: Rgb888) -> Rgb888 {
+        if self.stats.scale >= 1.0 {
+            return color;
+        }
+        let channel = |c: u8| (c as f32 * self.stats.scale) as u8;
+
+        Rgb888::new(channel(color.r()), channel(color.g()), channel(color.b()))
+    }
+}
+
+// Tells when frames start or stop being limited, at most once per `REPORT_INTERVAL`.
+#[derive(Default)]
+pub struct PowerReport {
+    limited: bool,
+    last_report: Option<Instant>,
+}
+
+impl PowerReport {
+    // Returns the line to log when limiting started or stopped since the last one.
+    pub fn update(&mut self, stats: PowerStats, instant: Instant) -> Option<String> {
+        let limited = stats.scale < 1.0;
+        if limited == self.limited
+            || self
+                .last_report
+                .is_some_and(|last| instant - last < REPORT_INTERVAL)
+        {
+            return None;
+        }
+        self.limited = limited;
+        self.last_report = Some(instant);
+
+        Some(match limited {
+            true => format!(
+                "frame needs {:.1} A, dimming to {:.0}% ({} of {} frames limited)",
+                stats.estimated_amps,
+                stats.scale * 100.0,
+                stats.limited_frames,
+                stats.frames
+            ),
+            false => "frames are within the power budget again".to_string(),
+        })
+    }
+}
+
+// Keeps the limiter's stats in a file for a metrics collector, like the textfile collector of the
+// Prometheus node exporter, rewritten at most once per `STATS_INTERVAL`.
+pub struct StatsFile {
+    path: PathBuf,
+    last_write: Option<Instant>,
+}
+
+impl StatsFile {
+    pub fn new(path: PathBuf) -> Self {
+        StatsFile {
+            path,
+            last_write: None,
+        }
+    }
+
+    pub fn update(&mut self, stats: PowerStats, instant: Instant) -> io::Result<()> {
+        if self
+            .last_write
+            .is_some_and(|last| instant - last < STATS_INTERVAL)
+        {
+            return Ok(());
+        }
+        self.last_write = Some(instant);
+
+        // Renamed into place, so the collector never reads half a file.
+        let written = self.path.with_extension("tmp");
+        fs::write(&written, stats.to_metrics())?;
+        fs::rename(&written, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+
+    use super::{PowerLimiter, PowerReport, StatsFile};
+    use crate::test_util;
+
+    #[test]
+    fn leaves_frames_within_the_budget_alone() {
+        let mut limiter = PowerLimiter::new(Some(2.0), 0.65);
+        limiter.update(&[Rgb888::WHITE; 512]);
+
+        assert_eq!(limiter.apply(Rgb888::WHITE), Rgb888::WHITE);
+        assert_eq!(limiter.stats().limited_frames, 0);
+    }
+
+    #[test]
+    fn scales_bright_frames_down_to_the_budget() {
+        let mut limiter = PowerLimiter::new(Some(2.0), 0.65);
+        limiter.update(&[Rgb888::WHITE; 2048]);
+
+        let stats = limiter.stats();
+        assert!((stats.estimated_amps - 3.99).abs() < 0.01);
+        assert!((stats.scale - 0.5).abs() < 0.01);
+        assert_eq!(stats.limited_frames, 1);
+        assert_eq!(limiter.apply(Rgb888::WHITE), Rgb888::new(127, 127, 127));
+
+        limiter.update(&[Rgb888::BLACK; 2048]);
+        assert_eq!(limiter.apply(Rgb888::WHITE), Rgb888::WHITE);
+        assert_eq!(limiter.stats().frames, 2);
+    }
+
+    #[test]
+    fn reports_limiting_at_most_once_a_minute() {
+        let mut limiter = PowerLimiter::new(Some(2.0), 0.65);
+        let mut report = PowerReport::default();
+        let start = Instant::now();
+        let mut lines = Vec::new();
+
+        for second in 0..90 {
+            let frame = match second % 2 {
+                0 => [Rgb888::WHITE; 2048],
+                _ => [Rgb888::BLACK; 2048],
+            };
+            limiter.update(&frame);
+            let instant = start + Duration::from_secs(second);
+            lines.extend(report.update(limiter.stats(), instant));
+        }
+
+        assert_eq!(
+            lines,
+            [
+                "frame needs 4.0 A, dimming to 50% (1 of 1 frames limited)",
+                "frames are within the power budget again"
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_the_stats_for_a_metrics_collector() {
+        let dir = test_util::temp_dir();
+        let path = dir.join("lumi-dash.prom");
+        let mut file = StatsFile::new(path.clone());
+        let mut limiter = PowerLimiter::new(Some(2.0), 0.65);
+        let start = Instant::now();
+
+        limiter.update(&[Rgb888::WHITE; 2048]);
+        file.update(limiter.stats(), start).unwrap();
+        limiter.update(&[Rgb888::BLACK; 2048]);
+        file.update(limiter.stats(), start + Duration::from_secs(5))
+            .unwrap();
+
+        let metrics = fs::read_to_string(&path).unwrap();
+        assert!(metrics.contains("# TYPE lumi_dash_power_scale gauge\nlumi_dash_power_scale 0.5"));
+        assert!(metrics.contains("lumi_dash_power_limited_frames_total 1\n"));
+        assert!(metrics.contains("lumi_dash_frames_total 1\n"));
+
+        file.update(limiter.stats(), start + Duration::from_secs(10))
+            .unwrap();
+        let metrics = fs::read_to_string(&path).unwrap();
+        assert!(metrics.contains("lumi_dash_frames_total 2\n"));
+    }
+}
@@ -0,0 +1,110 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local, TimeZone};
+use embedded_graphics::prelude::*;
+use image::{Rgb, RgbImage};
+
+use super::framebuffer::Framebuffer;
+use super::pixel_display::{DisplayMode, PixelDisplay};
+use crate::config::PanelConfig;
+
+// Set LUMI_UPDATE_SNAPSHOTS=1 to overwrite the reference images with the current output.
+const UPDATE_ENV: &str = "LUMI_UPDATE_SNAPSHOTS";
+
+pub fn display() -> PixelDisplay {
+    display_for(&PanelConfig::default())
+}
+
+pub fn display_for(panel: &PanelConfig) -> PixelDisplay {
+    PixelDisplay::new(panel, DisplayMode::Headless)
+}
+
+pub fn fixed_instant() -> DateTime<Local> {
+    Local.with_ymd_and_hms(2024, 4, 5, 13, 37, 0).unwrap()
+}
+
+pub fn assert_snapshot(name: &str, display: &PixelDisplay) {
+    let frame = display.framebuffer();
+    let actual = frame.to_image();
+
+    let reference_path = snapshot_dir().join(format!("{}.png", name));
+    if env::var_os(UPDATE_ENV).is_some() {
+        fs::create_dir_all(snapshot_dir()).unwrap();
+        frame.save_png(&reference_path).unwrap();
+        println!("recorded snapshot {}", reference_path.display());
+        return;
+    }
+
+    let diff_dir = diff_dir();
+    if !reference_path.exists() {
+        fs::create_dir_all(&diff_dir).unwrap();
+        let actual_path = diff_dir.join(format!("{}.actual.png", name));
+        actual.save(&actual_path).unwrap();
+        panic!(
+            "snapshot {} has no reference at {}, see {} and run with {}=1 to record it",
+            name,
+            reference_path.display(),
+            actual_path.display(),
+            UPDATE_ENV
+        );
+    }
+
+    let reference = image::open(&reference_path).unwrap().to_rgb8();
+    let mismatches = count_mismatches(&reference, frame);
+    if mismatches == 0 {
+        return;
+    }
+
+    fs::create_dir_all(&diff_dir).unwrap();
+    let actual_path = diff_dir.join(format!("{}.actual.png", name));
+    let diff_path = diff_dir.join(format!("{}.diff.png", name));
+    actual.save(&actual_path).unwrap();
+    diff_image(&reference, &actual).save(&diff_path).unwrap();
+
+    panic!(
+        "snapshot {} differs in {} pixels, see {} and {}",
+        name,
+        mismatches,
+        actual_path.display(),
+        diff_path.display()
+    );
+}
+
+fn count_mismatches(reference: &RgbImage, frame: &Framebuffer) -> usize {
+    let size = frame.size();
+    if reference.width() != size.width || reference.height() != size.height {
+        return (size.width * size.height) as usize;
+    }
+
+    reference
+        .enumerate_pixels()
+        .filter(|(x, y, pixel)| {
+            let color = frame.pixel(Point::new(*x as i32, *y as i32)).unwrap();
+            pixel.0 != [color.r(), color.g(), color.b()]
+        })
+        .count()
+}
+
+// Matching pixels are dimmed to grey, mismatching pixels are drawn in red.
+fn diff_image(reference: &RgbImage, actual: &RgbImage) -> RgbImage {
+    RgbImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let pixel = actual.get_pixel(x, y);
+        match reference.get_pixel_checked(x, y) {
+            Some(expected) if expected == pixel => {
+                let luma = (pixel.0.iter().map(|c| *c as u32).sum::<u32>() / 12) as u8;
+                Rgb([luma, luma, luma])
+            }
+            _ => Rgb([255, 0, 0]),
+        }
+    })
+}
+
+fn snapshot_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots")
+}
+
+fn diff_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/snapshot-diffs")
+}
@@ -0,0 +1,62 @@
+use std::convert::Infallible;
+
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle, StyledDrawable, Triangle};
+
+use super::font::Font;
+use super::framebuffer::Framebuffer;
+use super::text_layout::TextBox;
+use super::text_style::{contrasting, TextColor, TextStyle};
+
+// Anything modules can draw on, the panel as well as off-screen layers.
+pub trait Surface: DrawTarget<Color = Rgb888, Error = Infallible> + Sized {
+    // Draws `layer` with its top left corner at `position`, its alpha scaled by `opacity`.
+    fn blit(&mut self, layer: &Framebuffer, position: Point, opacity: u8);
+
+    // What is already drawn at `point`, text in the automatic color contrasts with it.
+    fn pixel(&self, point: Point) -> Option<Rgb888>;
+
+    fn draw_text(&mut self, text_str: &str, point: Point) {
+        self.draw_colored_text(text_str, point, Rgb888::WHITE);
+    }
+
+    fn draw_colored_text(&mut self, text_str: &str, point: Point, color: Rgb888) {
+        Font::default().draw(text_str, point, color, self);
+    }
+
+    fn draw_styled_text(&mut self, text_str: &str, point: Point, style: &TextStyle) {
+        let color = self.text_color(text_str, point, style);
+        style.draw(text_str, point, color, self);
+    }
+
+    // Draws `text_str` laid out in `text_box` and cut off at its edges, returns the size it takes.
+    fn draw_text_box(&mut self, text_str: &str, text_box: &TextBox, style: &TextStyle) -> Size {
+        let layout = text_box.layout(text_str, &style.font);
+        for (line, point) in &layout.lines {
+            let color = self.text_color(line, *point, style);
+            style.draw(line, *point, color, &mut self.clipped(&text_box.bounds));
+        }
+
+        layout.size
+    }
+
+    // Automatic colors are picked against what is already drawn under the text.
+    fn text_color(&self, text_str: &str, point: Point, style: &TextStyle) -> Rgb888 {
+        match style.color {
+            TextColor::Fixed(color) => color,
+            TextColor::Auto => {
+                let bounds = style.font.bounding_box(text_str, point);
+                contrasting(bounds.points().filter_map(|p| self.pixel(p)))
+            }
+        }
+    }
+
+    fn draw_line(&mut self, line: Line, style: PrimitiveStyle<Rgb888>) {
+        line.draw_styled(&style, self).ok();
+    }
+
+    fn draw_triangle(&mut self, triangle: Triangle, style: PrimitiveStyle<Rgb888>) {
+        triangle.draw_styled(&style, self).ok();
+    }
+}
@@ -0,0 +1,207 @@
+use std::convert::Infallible;
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*, primitives::Rectangle};
+
+use super::framebuffer::Framebuffer;
+use crate::apps::launcher::Input;
+
+// Every character cell shows two pixels, the top one as foreground and the bottom one as background.
+const HALF_BLOCK: char = '▀';
+
+pub enum TerminalEvent {
+    Quit,
+    Input(Input),
+}
+
+pub struct TerminalDisplay {
+    frame: Framebuffer,
+    stdout: Stdout,
+}
+
+impl TerminalDisplay {
+    pub fn new(size: Size) -> Self {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode().expect("Terminal doesn't support raw mode");
+        execute!(
+            stdout,
+            terminal::EnterAlternateScreen,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::Hide
+        )
+        .expect("Terminal initialization failed");
+
+        TerminalDisplay {
+            frame: Framebuffer::new(size),
+            stdout,
+        }
+    }
+
+    pub fn update(&mut self) {
+        let mut out = self.stdout.lock();
+        render(&self.frame, &mut out).ok();
+        out.flush().ok();
+    }
+
+    pub fn events(&mut self) -> Vec<TerminalEvent> {
+        let mut events = Vec::new();
+        while event::poll(Duration::ZERO).unwrap_or(false) {
+            if let Ok(Event::Key(key)) = event::read() {
+                events.extend(map_key(key));
+            }
+        }
+
+        events
+    }
+}
+
+impl Drop for TerminalDisplay {
+    fn drop(&mut self) {
+        execute!(
+            self.stdout,
+            ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        )
+        .ok();
+        terminal::disable_raw_mode().ok();
+    }
+}
+
+impl OriginDimensions for TerminalDisplay {
+    fn size(&self) -> Size {
+        self.frame.size()
+    }
+}
+
+impl DrawTarget for TerminalDisplay {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.frame.draw_iter(pixels)
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.frame.fill_solid(area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.frame.clear(color)
+    }
+}
+
+fn render<W: Write>(frame: &Framebuffer, out: &mut W) -> io::Result<()> {
+    let size = frame.size();
+
+    for row in 0..(size.height as i32 + 1) / 2 {
+        queue!(out, cursor::MoveTo(0, row as u16))?;
+        for x in 0..size.width as i32 {
+            let top = frame.pixel(Point::new(x, row * 2)).unwrap_or(Rgb888::BLACK);
+            let bottom = frame
+                .pixel(Point::new(x, row * 2 + 1))
+                .unwrap_or(Rgb888::BLACK);
+            queue!(
+                out,
+                SetForegroundColor(color(top)),
+                SetBackgroundColor(color(bottom)),
+                Print(HALF_BLOCK)
+            )?;
+        }
+        queue!(out, ResetColor)?;
+    }
+
+    Ok(())
+}
+
+fn color(color: Rgb888) -> Color {
+    Color::Rgb {
+        r: color.r(),
+        g: color.g(),
+        b: color.b(),
+    }
+}
+
+// Uses the same keys as the simulator window.
+fn map_key(key: KeyEvent) -> Option<TerminalEvent> {
+    if key.kind != KeyEventKind::Press {
+        return None;
+    }
+
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(TerminalEvent::Quit)
+        }
+        KeyCode::Char('q') | KeyCode::Esc => Some(TerminalEvent::Quit),
+        KeyCode::Left => Some(TerminalEvent::Input(Input::Next)),
+        KeyCode::Right => Some(TerminalEvent::Input(Input::Prev)),
+        KeyCode::Down | KeyCode::Enter => Some(TerminalEvent::Input(Input::Pressed)),
+        KeyCode::Up => Some(TerminalEvent::Input(Input::Held)),
+        KeyCode::Char('b') => Some(TerminalEvent::Input(Input::Brightness)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use embedded_graphics::{pixelcolor::Rgb888, prelude::*};
+
+    use super::{map_key, render, TerminalEvent};
+    use crate::apps::launcher::Input;
+    use crate::pixel_display::framebuffer::Framebuffer;
+
+    #[test]
+    fn renders_two_pixels_per_cell() {
+        let mut frame = Framebuffer::new(Size::new(1, 2));
+        Pixel(Point::new(0, 0), Rgb888::RED)
+            .draw(&mut frame)
+            .unwrap();
+        Pixel(Point::new(0, 1), Rgb888::BLUE)
+            .draw(&mut frame)
+            .unwrap();
+
+        let mut out = Vec::new();
+        render(&frame, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("\x1b[38;2;255;0;0m"));
+        assert!(out.contains("\x1b[48;2;0;0;255m"));
+        assert_eq!(out.matches('▀').count(), 1);
+    }
+
+    #[test]
+    fn maps_arrow_keys_to_inputs() {
+        let key = |code| map_key(KeyEvent::new(code, KeyModifiers::NONE));
+
+        assert!(matches!(
+            key(KeyCode::Left),
+            Some(TerminalEvent::Input(Input::Next))
+        ));
+        assert!(matches!(
+            key(KeyCode::Right),
+            Some(TerminalEvent::Input(Input::Prev))
+        ));
+        assert!(matches!(
+            key(KeyCode::Down),
+            Some(TerminalEvent::Input(Input::Pressed))
+        ));
+        assert!(matches!(
+            key(KeyCode::Up),
+            Some(TerminalEvent::Input(Input::Held))
+        ));
+        assert!(matches!(
+            key(KeyCode::Char('b')),
+            Some(TerminalEvent::Input(Input::Brightness))
+        ));
+        assert!(matches!(key(KeyCode::Char('q')), Some(TerminalEvent::Quit)));
+        assert!(key(KeyCode::Char('x')).is_none());
+    }
+}
@@ -0,0 +1,257 @@
+use std::mem;
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+
+use super::font::Font;
+
+const ELLIPSIS: &str = "...";
+
+// Where lines go on either axis of the box, the start is the left or top side.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Alignment {
+    Start,
+    Center,
+    End,
+}
+
+// What happens to lines wider than the box.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Overflow {
+    // Cut off at the edge of the box.
+    Clip,
+    // Shortened to end in "...".
+    Ellipsis,
+    // Broken between words, or inside words too long for a line.
+    Wrap,
+}
+
+// A rectangle to lay text out in. Lines past `max_lines` or the height of the box are left out,
+// with an ellipsis or wrapping the last line shown ends in "..." then.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextBox {
+    pub bounds: Rectangle,
+    pub horizontal: Alignment,
+    pub vertical: Alignment,
+    pub max_lines: Option<u32>,
+    pub overflow: Overflow,
+}
+
+// The lines to draw with the baseline point of each, and the size they take up in the box.
+#[derive(PartialEq, Debug)]
+pub struct TextLayout {
+    pub lines: Vec<(String, Point)>,
+    pub size: Size,
+}
+
+impl TextBox {
+    pub fn new(bounds: Rectangle) -> Self {
+        TextBox {
+            bounds,
+            horizontal: Alignment::Start,
+            vertical: Alignment::Start,
+            max_lines: None,
+            overflow: Overflow::Clip,
+        }
+    }
+
+    pub fn with_alignment(self, horizontal: Alignment, vertical: Alignment) -> Self {
+        TextBox {
+            horizontal,
+            vertical,
+            ..self
+        }
+    }
+
+    pub fn with_max_lines(self, max_lines: u32) -> Self {
+        TextBox {
+            max_lines: Some(max_lines),
+            ..self
+        }
+    }
+
+    pub fn with_overflow(self, overflow: Overflow) -> Self {
+        TextBox { overflow, ..self }
+    }
+
+    pub fn layout(&self, text: &str, font: &Font) -> TextLayout {
+        let Size { width, height } = self.bounds.size;
+        let line_height = font.line_height();
+        let fit = (height / line_height.max(1)).max(1);
+        let max_lines = self.max_lines.map_or(fit, |max| max.min(fit)) as usize;
+
+        let mut lines: Vec<String> = match self.overflow {
+            Overflow::Wrap => text
+                .lines()
+                .flat_map(|line| wrap(line, width, font))
+                .collect(),
+            _ => text.lines().map(str::to_string).collect(),
+        };
+        let cut = lines.len() > max_lines;
+        lines.truncate(max_lines);
+        if self.overflow == Overflow::Ellipsis {
+            for line in lines.iter_mut() {
+                *line = ellipsize(line, width, font, false);
+            }
+        }
+        if let (true, Overflow::Ellipsis | Overflow::Wrap, Some(last)) =
+            (cut, self.overflow, lines.last_mut())
+        {
+            *last = ellipsize(last, width, font, true);
+        }
+
+        let widths: Vec<u32> = lines.iter().map(|line| font.text_width(line)).collect();
+        let text_height = lines.len() as u32 * line_height;
+        let top = self.bounds.top_left.y + offset(self.vertical, height, text_height);
+        let baseline = top + font.ascent() as i32 - 1;
+
+        let lines = lines
+            .into_iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (line, &line_width))| {
+                let x = self.bounds.top_left.x + offset(self.horizontal, width, line_width);
+                (
+                    line,
+                    Point::new(x, baseline + (i as u32 * line_height) as i32),
+                )
+            })
+            .collect();
+
+        TextLayout {
+            lines,
+            size: Size::new(
+                widths.into_iter().max().unwrap_or(0).min(width),
+                text_height.min(height),
+            ),
+        }
+    }
+}
+
+// Text longer than the room sticks out on the side it isn't aligned to, or both when centered.
+fn offset(alignment: Alignment, room: u32, length: u32) -> i32 {
+    match alignment {
+        Alignment::Start => 0,
+        Alignment::Center => (room as i32 - length as i32) / 2,
+        Alignment::End => room as i32 - length as i32,
+    }
+}
+
+// Greedy, every line takes as many words as fit.
+fn wrap(text: &str, width: u32, font: &Font) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        let joined = match line.is_empty() {
+            true => word.to_string(),
+            false => format!("{} {}", line, word),
+        };
+        if font.text_width(&joined) <= width {
+            line = joined;
+            continue;
+        }
+
+        if !line.is_empty() {
+            lines.push(mem::take(&mut line));
+        }
+        for c in word.chars() {
+            line.push(c);
+            if line.chars().count() > 1 && font.text_width(&line) > width {
+                line.pop();
+                lines.push(mem::replace(&mut line, c.to_string()));
+            }
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+// `cut` adds the ellipsis even if the line fits, to show that lines after it were left out.
+fn ellipsize(line: &str, width: u32, font: &Font, cut: bool) -> String {
+    if !cut && font.text_width(line) <= width {
+        return line.to_string();
+    }
+
+    let mut line = line.trim_end().to_string();
+    while !line.is_empty() && font.text_width(&format!("{}{}", line, ELLIPSIS)) > width {
+        line.pop();
+        line.truncate(line.trim_end().len());
+    }
+    line + ELLIPSIS
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::geometry::{Point, Size};
+    use embedded_graphics::primitives::Rectangle;
+
+    use super::{Alignment, Overflow, TextBox, TextLayout};
+    use crate::pixel_display::font::Font;
+
+    // The default font is 4x6 with the baseline on the fifth row.
+    fn text_box(width: u32, height: u32) -> TextBox {
+        TextBox::new(Rectangle::new(Point::new(10, 0), Size::new(width, height)))
+    }
+
+    fn lines(layout: &TextLayout) -> Vec<&str> {
+        layout.lines.iter().map(|(line, _)| line.as_str()).collect()
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        let layout = text_box(30, 32)
+            .with_overflow(Overflow::Wrap)
+            .layout("Night Drive to Neon", &Font::default());
+
+        assert_eq!(lines(&layout), ["Night", "Drive", "to Neon"]);
+        assert_eq!(layout.lines[1].1, Point::new(10, 10));
+        assert_eq!(layout.size, Size::new(28, 18));
+    }
+
+    #[test]
+    fn breaks_words_longer_than_a_line() {
+        let layout = text_box(16, 32)
+            .with_overflow(Overflow::Wrap)
+            .layout("Supersonic", &Font::default());
+
+        assert_eq!(lines(&layout), ["Supe", "rson", "ic"]);
+    }
+
+    #[test]
+    fn ends_cut_text_with_an_ellipsis() {
+        let font = Font::default();
+        let wrapped = text_box(30, 32)
+            .with_overflow(Overflow::Wrap)
+            .with_max_lines(2)
+            .layout("Night Drive to Neon", &font);
+        assert_eq!(lines(&wrapped), ["Night", "Driv..."]);
+
+        let shortened = text_box(30, 6)
+            .with_overflow(Overflow::Ellipsis)
+            .layout("Night Drive", &font);
+        assert_eq!(lines(&shortened), ["Nigh..."]);
+
+        let clipped = text_box(30, 6).layout("Night Drive", &font);
+        assert_eq!(lines(&clipped), ["Night Drive"]);
+        assert_eq!(clipped.size, Size::new(30, 6));
+    }
+
+    #[test]
+    fn aligns_lines_in_the_box() {
+        let font = Font::default();
+        let centered = text_box(30, 20)
+            .with_alignment(Alignment::Center, Alignment::Center)
+            .layout("Lumi", &font);
+        assert_eq!(centered.lines[0].1, Point::new(17, 11));
+
+        let bottom_right = text_box(30, 20)
+            .with_alignment(Alignment::End, Alignment::End)
+            .layout("Lumi\nDash", &font);
+        assert_eq!(bottom_right.lines[0].1, Point::new(24, 12));
+        assert_eq!(bottom_right.lines[1].1, Point::new(24, 18));
+    }
+}
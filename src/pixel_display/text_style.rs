@@ -0,0 +1,119 @@
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+
+use super::font::Font;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextColor {
+    Fixed(Rgb888),
+    // Black or white, whichever stands out more against what is under the text.
+    Auto,
+}
+
+// How text is drawn, the outline and shadow keep it readable over busy backgrounds.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TextStyle {
+    pub font: Font,
+    pub color: TextColor,
+    pub outline: Option<Rgb888>,
+    pub shadow: Option<Rgb888>,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle::new(Rgb888::WHITE)
+    }
+}
+
+impl TextStyle {
+    pub fn new(color: Rgb888) -> Self {
+        TextStyle {
+            font: Font::default(),
+            color: TextColor::Fixed(color),
+            outline: None,
+            shadow: None,
+        }
+    }
+
+    pub fn with_font(self, font: Font) -> Self {
+        TextStyle { font, ..self }
+    }
+
+    pub fn with_outline(self, outline: Rgb888) -> Self {
+        TextStyle {
+            outline: Some(outline),
+            ..self
+        }
+    }
+
+    pub fn with_shadow(self, shadow: Rgb888) -> Self {
+        TextStyle {
+            shadow: Some(shadow),
+            ..self
+        }
+    }
+
+    // The shadow is the text drawn one pixel down and right, the outline is the text drawn one
+    // pixel off in every direction, both underneath the text drawn in `color`.
+    pub fn draw<D: DrawTarget<Color = Rgb888>>(
+        &self,
+        text: &str,
+        point: Point,
+        color: Rgb888,
+        target: &mut D,
+    ) {
+        if let Some(shadow) = self.shadow {
+            self.font
+                .draw(text, point + Point::new(1, 1), shadow, target);
+        }
+        if let Some(outline) = self.outline {
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    if dx != 0 || dy != 0 {
+                        self.font
+                            .draw(text, point + Point::new(dx, dy), outline, target);
+                    }
+                }
+            }
+        }
+        self.font.draw(text, point, color, target);
+    }
+}
+
+// Black over light backgrounds and white over dark ones, by their average perceived brightness.
+pub fn contrasting(background: impl Iterator<Item = Rgb888>) -> Rgb888 {
+    let (sum, count) = background.fold((0u32, 0u32), |(sum, count), color| {
+        let luma = 299 * color.r() as u32 + 587 * color.g() as u32 + 114 * color.b() as u32;
+        (sum + luma / 1000, count + 1)
+    });
+
+    match count {
+        0 => Rgb888::WHITE,
+        _ if sum / count > 127 => Rgb888::BLACK,
+        _ => Rgb888::WHITE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+
+    use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+
+    use super::contrasting;
+
+    #[test]
+    fn contrasts_with_the_average_brightness() {
+        let pink = Rgb888::new(255, 183, 197);
+        let forest = Rgb888::new(20, 70, 30);
+
+        assert_eq!(contrasting(iter::repeat_n(pink, 8)), Rgb888::BLACK);
+        assert_eq!(contrasting(iter::repeat_n(forest, 8)), Rgb888::WHITE);
+        assert_eq!(
+            contrasting([Rgb888::WHITE, Rgb888::BLACK, Rgb888::BLACK].into_iter()),
+            Rgb888::WHITE
+        );
+        assert_eq!(contrasting(iter::empty()), Rgb888::WHITE);
+    }
+}
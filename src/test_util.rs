@@ -0,0 +1,60 @@
+use std::env;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::*;
+use image::codecs::gif::GifEncoder;
+use image::{Delay, Frame, Rgba, RgbaImage};
+
+// A directory of its own for each test, removed again when it is dropped, also if the test fails.
+pub struct TempDir(PathBuf);
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+pub fn temp_dir() -> TempDir {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let name = format!(
+        "lumi-dash-test-{}-{}",
+        process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    );
+    let dir = env::temp_dir().join(name);
+    fs::create_dir_all(&dir).unwrap();
+
+    TempDir(dir)
+}
+
+// Writes a GIF with one frame filled with each color, shown for the given milliseconds.
+pub fn write_gif(path: &Path, size: Size, frames: &[(Rgb888, u32)]) {
+    let frames = frames.iter().map(|&(color, ms)| {
+        Frame::from_parts(
+            RgbaImage::from_pixel(
+                size.width,
+                size.height,
+                Rgba([color.r(), color.g(), color.b(), 255]),
+            ),
+            0,
+            0,
+            Delay::from_numer_denom_ms(ms, 1),
+        )
+    });
+    GifEncoder::new(fs::File::create(path).unwrap())
+        .encode_frames(frames)
+        .unwrap();
+}